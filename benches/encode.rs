@@ -0,0 +1,163 @@
+//! Writing codes, then tree construction, encoding and decoding on text,
+//! binary and skewed data of several sizes. Throughput of the latter is
+//! reported in MB/s of uncompressed data; the compression ratio of every
+//! input is printed before their timings.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use compressor::{
+    compress, decompress, BitWriter, CompressOptions, HuffTree, TokenTable, Tokenizer,
+};
+
+/// Writing a whole packed code per symbol against the bit-by-bit loop it
+/// replaced, on the same symbols and codes.
+fn write_codes(c: &mut Criterion) {
+    let input = std::fs::read("huffman.txt").expect("huffman.txt in the crate root");
+    let tokens = Tokenizer::Bytes.split(&input).unwrap();
+    let (table, symbols) = TokenTable::build(&tokens);
+    let codes = HuffTree::from_weights(&table.weights).unwrap().codes();
+
+    let mut group = c.benchmark_group("write_codes");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+    group.bench_function("packed", |b| {
+        b.iter(|| {
+            let mut writer = BitWriter::new(Vec::with_capacity(input.len()));
+            for symbol in &symbols {
+                let code = codes[*symbol as usize];
+                writer.write_bits(code.value, code.len as u32).unwrap();
+            }
+            black_box(writer.finish().unwrap())
+        })
+    });
+    group.bench_function("bit_by_bit", |b| {
+        b.iter(|| {
+            let mut writer = BitWriter::new(Vec::with_capacity(input.len()));
+            for symbol in &symbols {
+                let code = codes[*symbol as usize];
+                for i in 0..code.len {
+                    writer.write_bit(code.bit(i)).unwrap();
+                }
+            }
+            black_box(writer.finish().unwrap())
+        })
+    });
+    group.finish();
+}
+
+const SIZES: [usize; 3] = [16 << 10, 256 << 10, 4 << 20];
+
+/// xorshift64, so binary and skewed inputs are the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// `huffman.txt` repeated up to `len` bytes.
+fn text(len: usize) -> Vec<u8> {
+    let text = std::fs::read("huffman.txt").expect("huffman.txt in the crate root");
+    text.iter().copied().cycle().take(len).collect()
+}
+
+/// Uniformly random bytes, which Huffman coding cannot shrink.
+fn binary(len: usize) -> Vec<u8> {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    (0..len).map(|_| rng.next() as u8).collect()
+}
+
+/// Byte `n` with probability 2^-(n+1), giving codes up to 32 bits long.
+fn skewed(len: usize) -> Vec<u8> {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    (0..len)
+        .map(|_| (rng.next() | 1 << 32).trailing_zeros() as u8)
+        .collect()
+}
+
+/// Kind, size and contents of a benchmark input.
+type Input = (&'static str, usize, Vec<u8>);
+
+fn inputs() -> Vec<Input> {
+    let mut inputs = Vec::new();
+    for size in SIZES {
+        inputs.push(("text", size, text(size)));
+        inputs.push(("binary", size, binary(size)));
+        inputs.push(("skewed", size, skewed(size)));
+    }
+    inputs
+}
+
+fn options() -> CompressOptions {
+    CompressOptions {
+        tokenizer: Tokenizer::Bytes,
+        ..CompressOptions::default()
+    }
+}
+
+fn print_ratios(inputs: &[Input]) {
+    println!(
+        "{:<8} {:>10} {:>12} {:>7}",
+        "input", "size", "compressed", "ratio"
+    );
+    for (name, size, input) in inputs {
+        let compressed = compress(input, &options()).unwrap();
+        println!(
+            "{:<8} {:>10} {:>12} {:>7.3}",
+            name,
+            size,
+            compressed.len(),
+            compressed.len() as f64 / *size as f64
+        );
+    }
+}
+
+fn codec(c: &mut Criterion) {
+    let inputs = inputs();
+    print_ratios(&inputs);
+
+    // Counting the tokens and building the tree from their weights.
+    let mut group = c.benchmark_group("tree");
+    group.sample_size(10);
+    for (name, size, input) in &inputs {
+        let tokens = Tokenizer::Bytes.split(input).unwrap();
+        group.throughput(Throughput::BytesDecimal(*size as u64));
+        group.bench_with_input(BenchmarkId::new(*name, size), &tokens, |b, tokens| {
+            b.iter(|| {
+                let (table, _) = TokenTable::build(black_box(tokens));
+                HuffTree::from_weights(&table.weights).unwrap()
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("encode");
+    group.sample_size(10);
+    for (name, size, input) in &inputs {
+        group.throughput(Throughput::BytesDecimal(*size as u64));
+        group.bench_with_input(BenchmarkId::new(*name, size), input, |b, input| {
+            b.iter(|| compress(black_box(input), &options()).unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(10);
+    for (name, size, input) in &inputs {
+        let compressed = compress(input, &options()).unwrap();
+        group.throughput(Throughput::BytesDecimal(*size as u64));
+        group.bench_with_input(
+            BenchmarkId::new(*name, size),
+            &compressed,
+            |b, compressed| b.iter(|| decompress(black_box(compressed)).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, write_codes, codec);
+criterion_main!(benches);
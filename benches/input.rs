@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::PathBuf;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use compressor::{compress, CompressOptions, Input, Tokenizer};
+
+const COPIES: usize = 16;
+
+/// `huffman.txt` repeated `COPIES` times, written once under `target/`.
+fn large_input() -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("huffman_x16.txt");
+    if !path.exists() {
+        let text = fs::read("huffman.txt").expect("huffman.txt in the crate root");
+        fs::write(&path, text.repeat(COPIES)).unwrap();
+    }
+    path
+}
+
+/// Peak resident set size in KiB since the last call, from `/proc` on Linux.
+fn peak_rss_kib() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let peak = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches(" kB")
+        .parse()
+        .ok();
+    // Writing 5 resets the peak.
+    fs::write("/proc/self/clear_refs", "5").ok()?;
+    peak
+}
+
+fn compress_file(c: &mut Criterion) {
+    let path = large_input();
+    let size = fs::metadata(&path).unwrap().len();
+    let options = CompressOptions {
+        tokenizer: Tokenizer::Bytes,
+        block_size: Some(1 << 20),
+        ..CompressOptions::default()
+    };
+
+    peak_rss_kib();
+    for mmap in [false, true] {
+        let input = Input::open(&path, mmap).unwrap();
+        black_box(compress(&input, &options).unwrap());
+        drop(input);
+        if let Some(peak) = peak_rss_kib() {
+            println!("mmap: {mmap}, peak RSS {} MiB", peak / 1024);
+        }
+    }
+
+    let mut group = c.benchmark_group("compress_file");
+    group.throughput(Throughput::Bytes(size));
+    group.sample_size(10);
+    group.bench_function("buffered", |b| {
+        b.iter(|| compress(&Input::read(&path).unwrap(), &options).unwrap())
+    });
+    group.bench_function("mapped", |b| {
+        b.iter(|| compress(&Input::map(&path).unwrap(), &options).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, compress_file);
+criterion_main!(benches);
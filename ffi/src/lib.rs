@@ -0,0 +1,4 @@
+//! Links the `huf_*` functions of `compressor::ffi` into
+//! `libcompressor_ffi.a` and `libcompressor_ffi.so`.
+
+pub use compressor::ffi::*;
@@ -0,0 +1,10 @@
+//! Arbitrary bytes as a `.huf` file: decoding may fail but must not panic,
+//! and neither may range reads through a block index.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = compressor::decompress(data);
+    let _ = compressor::decompress_range(data, 3, 100);
+});
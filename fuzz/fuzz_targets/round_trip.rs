@@ -0,0 +1,27 @@
+//! `decompress(compress(x)) == x` for arbitrary input; the first byte picks
+//! the options.
+#![no_main]
+
+use compressor::{compress, decompress, CompressOptions, Tokenizer};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((flags, input)) = data.split_first() else {
+        return;
+    };
+    let options = CompressOptions {
+        tokenizer: match flags & 3 {
+            0 => Tokenizer::Bytes,
+            1 => Tokenizer::Words { min_count: 2 },
+            2 => Tokenizer::NGrams(3),
+            _ => Tokenizer::Chars,
+        },
+        order1: flags & 4 != 0,
+        rle: flags & 8 != 0,
+        block_size: (flags & 16 != 0).then_some(*flags as usize),
+    };
+    match compress(input, &options) {
+        Ok(compressed) => assert_eq!(decompress(&compressed).unwrap(), input),
+        Err(_) => assert_eq!(options.tokenizer, Tokenizer::Chars),
+    }
+});
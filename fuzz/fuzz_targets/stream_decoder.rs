@@ -0,0 +1,20 @@
+//! Arbitrary bytes fed to the streaming decoder in pieces; the first byte
+//! picks the piece size.
+#![no_main]
+
+use compressor::BlockDecoder;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((chunk, data)) = data.split_first() else {
+        return;
+    };
+    let mut decoder = BlockDecoder::new();
+    let mut output = Vec::new();
+    for piece in data.chunks(*chunk as usize + 1) {
+        if decoder.update(piece, &mut output).is_err() {
+            return;
+        }
+    }
+    let _ = decoder.finish();
+});
@@ -0,0 +1,94 @@
+/* C interface of the compressor crate, see src/ffi.rs. Written by hand,
+ * tests/ffi.rs checks it against the exported functions and status values. */
+
+#ifndef COMPRESSOR_H
+#define COMPRESSOR_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum HufStatus {
+  HUF_OK = 0,
+  /* A required pointer was null or the context was already finished. */
+  HUF_INVALID_ARGUMENT = 1,
+  /* Unknown tokenizer name, or input that the tokenizer rejects. */
+  HUF_TOKENIZER = 2,
+  HUF_TOO_FEW_SYMBOLS = 3,
+  HUF_INVALID_HEADER = 4,
+  HUF_INVALID_PAYLOAD = 5,
+  HUF_IO = 6,
+  /* The library panicked; this is a bug. */
+  HUF_PANIC = 7,
+  /* The input is an encrypted .huf file, which this interface cannot decrypt. */
+  HUF_ENCRYPTED = 8,
+  HUF_AUTHENTICATION = 9,
+  /* A block of the input does not match its checksum. */
+  HUF_CHECKSUM = 10,
+} HufStatus;
+
+/* Bytes allocated by the library, release with huf_buffer_free. */
+typedef struct HufBuffer {
+  uint8_t *data;
+  size_t len;
+} HufBuffer;
+
+typedef struct HufOptions {
+  /* Tokenizer name as accepted by --tokenizer, NULL for "chars". */
+  const char *tokenizer;
+  bool order1;
+  bool rle;
+  /* Block size in bytes, 0 for no blocks (or the default block size for a
+   * streaming encoder). */
+  size_t block_size;
+} HufOptions;
+
+typedef struct HufEncoder HufEncoder;
+typedef struct HufDecoder HufDecoder;
+
+/* Compresses len bytes at input into out. options may be NULL. */
+HufStatus huf_compress(const uint8_t *input, size_t len, const HufOptions *options, HufBuffer *out);
+
+/* Decompresses the .huf data at input into out. */
+HufStatus huf_decompress(const uint8_t *input, size_t len, HufBuffer *out);
+
+/* Releases a buffer returned by the library. Null buffers are ignored. */
+void huf_buffer_free(HufBuffer buffer);
+
+/* Creates a streaming encoder that writes blocked output; NULL if the options
+ * are invalid. */
+HufEncoder *huf_encoder_new(const HufOptions *options);
+
+/* Feeds len bytes to the encoder; out receives the blocks they complete,
+ * possibly none. */
+HufStatus huf_encoder_update(HufEncoder *encoder, const uint8_t *input, size_t len, HufBuffer *out);
+
+/* Writes the last block and the block index to out. The encoder cannot be fed
+ * afterwards but still has to be freed. */
+HufStatus huf_encoder_finish(HufEncoder *encoder, HufBuffer *out);
+
+void huf_encoder_free(HufEncoder *encoder);
+
+HufDecoder *huf_decoder_new(void);
+
+/* Feeds len compressed bytes to the decoder; out receives the data of the
+ * blocks they complete, possibly none. */
+HufStatus huf_decoder_update(HufDecoder *decoder, const uint8_t *input, size_t len, HufBuffer *out);
+
+/* Checks that the compressed data did not end in the middle of a block. */
+HufStatus huf_decoder_finish(HufDecoder *decoder);
+
+void huf_decoder_free(HufDecoder *decoder);
+
+/* Static, NUL-terminated description of a status code. */
+const char *huf_status_message(int status);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* COMPRESSOR_H */
@@ -0,0 +1,46 @@
+import pytest
+
+import compressor
+
+TEXT = "abracadabra, the quick brown fox jumps over the lazy dog"
+
+
+@pytest.mark.parametrize("tokenizer", ["bytes", "chars", "words", "ngram:2"])
+def test_round_trip(tokenizer):
+    compressed = compressor.compress(TEXT, tokenizer=tokenizer)
+    assert isinstance(compressed, bytes)
+    assert compressor.decompress(compressed) == TEXT.encode()
+
+
+def test_round_trip_binary_with_options():
+    data = bytes(range(256)) * 8 + b"\x00" * 500
+    compressed = compressor.compress(data, tokenizer="bytes", order1=True, rle=True, block_size=512)
+    assert compressor.decompress(compressed) == data
+
+
+def test_round_trip_single_symbol_and_empty():
+    for data in ["aaaa", ""]:
+        assert compressor.decompress(compressor.compress(data)) == data.encode()
+    assert compressor.build_code_table("aaaa") == {"a": "0"}
+
+
+def test_code_table_matches_frequencies():
+    codes = compressor.build_code_table("abracadabra")
+    assert codes == {"a": "0", "b": "111", "c": "101", "d": "100", "r": "110"}
+
+
+def test_code_table_keeps_binary_tokens_as_bytes():
+    codes = compressor.build_code_table(b"\xff\xfe\xff", tokenizer="bytes")
+    assert set(codes) == {b"\xfe", b"\xff"}
+
+
+def test_frequencies():
+    assert compressor.frequencies("abracadabra") == {"a": 5, "b": 2, "c": 1, "d": 1, "r": 2}
+    assert compressor.frequencies(b"\x00\x00\x01") == {0: 2, 1: 1}
+
+
+def test_errors_raise_value_error():
+    with pytest.raises(ValueError, match="Unknown tokenizer"):
+        compressor.compress(TEXT, tokenizer="lines")
+    with pytest.raises(ValueError, match="Invalid header"):
+        compressor.decompress(b"12\nnot json\n\n")
@@ -0,0 +1,235 @@
+//! Tokio adapters over [`BlockEncoder`] and [`BlockDecoder`], with the
+//! `tokio` feature.
+//!
+//! The codec runs inside `poll_write` and `poll_read`, at most one block per
+//! call on the encoding side, so the block size bounds how long a task holds
+//! its worker thread.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{BlockDecoder, BlockEncoder, CompressOptions};
+
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Writes the blocked `.huf` form of everything written to it into `W`.
+/// `shutdown` writes the last block and the block index; data written
+/// without shutting down is lost.
+pub struct HuffmanEncoder<W> {
+    inner: W,
+    encoder: Option<BlockEncoder>,
+    /// Compressed bytes not yet taken by `inner`, from `position` on.
+    output: Vec<u8>,
+    position: usize,
+}
+
+impl<W: AsyncWrite + Unpin> HuffmanEncoder<W> {
+    /// Blocks are `options.block_size` bytes, see [`BlockEncoder::new`].
+    pub fn new(inner: W, options: &CompressOptions) -> Self {
+        Self {
+            inner,
+            encoder: Some(BlockEncoder::new(options)),
+            output: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.position < self.output.len() {
+            let written =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &self.output[self.position..]))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.position += written;
+        }
+        self.output.clear();
+        self.position = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for HuffmanEncoder<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        let Some(encoder) = &mut this.encoder else {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "write after shutdown",
+            )));
+        };
+        let len = buf.len().min(encoder.block_size());
+        encoder.update(&buf[..len], &mut this.output)?;
+        Poll::Ready(Ok(len))
+    }
+
+    /// Writes out the completed blocks. The current block stays buffered,
+    /// cutting it short would change the output.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        if let Some(encoder) = this.encoder.take() {
+            encoder.finish(&mut this.output)?;
+            ready!(this.poll_drain(cx))?;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Reads the decompressed data of the `.huf` stream in `R`. A stream that
+/// ends in the middle of a block fails with `InvalidData`.
+pub struct HuffmanDecoder<R> {
+    inner: R,
+    decoder: Option<BlockDecoder>,
+    buffer: Box<[u8; READ_BUFFER_SIZE]>,
+    /// Decoded bytes not yet read, from `position` on.
+    output: Vec<u8>,
+    position: usize,
+}
+
+impl<R: AsyncRead + Unpin> HuffmanDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            decoder: Some(BlockDecoder::new()),
+            buffer: Box::new([0; READ_BUFFER_SIZE]),
+            output: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HuffmanDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.position < this.output.len() {
+                let len = buf.remaining().min(this.output.len() - this.position);
+                buf.put_slice(&this.output[this.position..this.position + len]);
+                this.position += len;
+                return Poll::Ready(Ok(()));
+            }
+            if this.decoder.is_none() {
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut read = ReadBuf::new(&mut this.buffer[..]);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read))?;
+            let len = read.filled().len();
+            this.output.clear();
+            this.position = 0;
+            if len == 0 {
+                this.decoder.take().unwrap().finish()?;
+            } else if let Some(decoder) = &mut this.decoder {
+                decoder.update(&this.buffer[..len], &mut this.output)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{compress, Tokenizer};
+
+    fn options() -> CompressOptions {
+        CompressOptions {
+            tokenizer: Tokenizer::Bytes,
+            block_size: Some(1000),
+            ..CompressOptions::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn duplex_streams_should_round_trip() {
+        let input = std::fs::read("huffman.txt").unwrap();
+        let (compressed_in, compressed_out) = duplex(4096);
+        let (plain_in, mut plain_out) = duplex(4096);
+
+        let writer = {
+            let input = input.clone();
+            tokio::spawn(async move {
+                let mut encoder = HuffmanEncoder::new(compressed_in, &options());
+                for chunk in input.chunks(777) {
+                    encoder.write_all(chunk).await.unwrap();
+                }
+                encoder.shutdown().await.unwrap();
+            })
+        };
+        let pipe = tokio::spawn(async move {
+            let mut decoder = HuffmanDecoder::new(compressed_out);
+            let mut plain_in = plain_in;
+            tokio::io::copy(&mut decoder, &mut plain_in).await.unwrap();
+            plain_in.shutdown().await.unwrap();
+        });
+
+        let mut decoded = Vec::new();
+        plain_out.read_to_end(&mut decoded).await.unwrap();
+        writer.await.unwrap();
+        pipe.await.unwrap();
+        assert_eq!(decoded, input);
+    }
+
+    #[tokio::test]
+    async fn encoder_should_match_compress() {
+        let input = std::fs::read("small.txt").unwrap();
+        let mut encoder = HuffmanEncoder::new(Vec::new(), &options());
+        encoder.write_all(&input).await.unwrap();
+        encoder.shutdown().await.unwrap();
+
+        assert_eq!(encoder.into_inner(), compress(&input, &options()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn truncated_stream_should_fail() {
+        let compressed = compress(b"abracadabra", &options()).unwrap();
+        let mut decoder = HuffmanDecoder::new(&compressed[..compressed.len() / 2]);
+
+        let err = decoder.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,292 @@
+use crate::io::{self, Read, Write};
+use crate::prelude::*;
+
+/// Order in which bits are packed into a byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    /// First bit goes into the most significant bit of the byte and a multi
+    /// bit value is written starting with its most significant bit. This is
+    /// what `.huf` payloads use.
+    #[default]
+    MsbFirst,
+    /// First bit goes into the least significant bit of the byte and a multi
+    /// bit value is written starting with its least significant bit, as in
+    /// DEFLATE.
+    LsbFirst,
+}
+
+fn mask(count: u32) -> u128 {
+    (1u128 << count) - 1
+}
+
+/// Packs bit fields of up to 64 bits into bytes and writes every completed
+/// byte to `W`. Call [`BitWriter::finish`] to pad and write the last byte.
+pub struct BitWriter<W: Write> {
+    inner: W,
+    order: BitOrder,
+    /// Pending bits, always fewer than 8 between calls.
+    buffer: u128,
+    buffered: u32,
+    bits_written: u64,
+}
+
+impl<W: Write> BitWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_order(inner, BitOrder::MsbFirst)
+    }
+
+    pub fn with_order(inner: W, order: BitOrder) -> Self {
+        Self {
+            inner,
+            order,
+            buffer: 0,
+            buffered: 0,
+            bits_written: 0,
+        }
+    }
+
+    /// Writes the low `count` bits of `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is larger than 64.
+    pub fn write_bits(&mut self, value: u64, count: u32) -> io::Result<()> {
+        assert!(count <= 64, "cannot write more than 64 bits at once");
+        let value = value as u128 & mask(count);
+        match self.order {
+            BitOrder::MsbFirst => self.buffer = (self.buffer << count) | value,
+            BitOrder::LsbFirst => self.buffer |= value << self.buffered,
+        }
+        self.buffered += count;
+        self.bits_written += count as u64;
+
+        let mut bytes = [0u8; 9];
+        let mut len = 0;
+        while self.buffered >= 8 {
+            self.buffered -= 8;
+            bytes[len] = match self.order {
+                BitOrder::MsbFirst => (self.buffer >> self.buffered) as u8,
+                BitOrder::LsbFirst => {
+                    let byte = self.buffer as u8;
+                    self.buffer >>= 8;
+                    byte
+                }
+            };
+            len += 1;
+        }
+        self.buffer &= mask(self.buffered);
+        self.inner.write_all(&bytes[..len])
+    }
+
+    pub fn write_bit(&mut self, bit: bool) -> io::Result<()> {
+        self.write_bits(bit as u64, 1)
+    }
+
+    /// Number of bits written so far, padding excluded.
+    pub fn bits_written(&self) -> u64 {
+        self.bits_written
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Pads the last partial byte with zero bits, writes it and returns the
+    /// inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.buffered > 0 {
+            let padding = 8 - self.buffered;
+            self.write_bits(0, padding)?;
+            self.bits_written -= padding as u64;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+const READ_BUFFER_SIZE: usize = 4096;
+
+/// Reads bit fields of up to 64 bits from `R`, the counterpart of
+/// [`BitWriter`]. Bytes are pulled from `R` in chunks, so wrapping it in a
+/// `BufReader` is not needed.
+pub struct BitReader<R: Read> {
+    inner: R,
+    order: BitOrder,
+    bytes: Box<[u8; READ_BUFFER_SIZE]>,
+    position: usize,
+    len: usize,
+    /// Bits taken from `bytes` but not handed out yet.
+    buffer: u128,
+    buffered: u32,
+    bits_read: u64,
+}
+
+impl<R: Read> BitReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_order(inner, BitOrder::MsbFirst)
+    }
+
+    pub fn with_order(inner: R, order: BitOrder) -> Self {
+        Self {
+            inner,
+            order,
+            bytes: Box::new([0; READ_BUFFER_SIZE]),
+            position: 0,
+            len: 0,
+            buffer: 0,
+            buffered: 0,
+            bits_read: 0,
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<u8> {
+        if self.position == self.len {
+            self.len = loop {
+                match self.inner.read(&mut self.bytes[..]) {
+                    Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    Ok(len) => break len,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            };
+            self.position = 0;
+        }
+        self.position += 1;
+        Ok(self.bytes[self.position - 1])
+    }
+
+    /// Reads `count` bits into the low bits of the result. Fails with
+    /// `UnexpectedEof` if the input ends first; the bits already buffered stay
+    /// available.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is larger than 64.
+    pub fn read_bits(&mut self, count: u32) -> io::Result<u64> {
+        assert!(count <= 64, "cannot read more than 64 bits at once");
+        while self.buffered < count {
+            let byte = self.next_byte()? as u128;
+            match self.order {
+                BitOrder::MsbFirst => self.buffer = (self.buffer << 8) | byte,
+                BitOrder::LsbFirst => self.buffer |= byte << self.buffered,
+            }
+            self.buffered += 8;
+        }
+
+        let value = match self.order {
+            BitOrder::MsbFirst => (self.buffer >> (self.buffered - count)) & mask(count),
+            BitOrder::LsbFirst => {
+                let value = self.buffer & mask(count);
+                self.buffer >>= count;
+                value
+            }
+        };
+        self.buffered -= count;
+        self.buffer &= mask(self.buffered);
+        self.bits_read += count as u64;
+        Ok(value as u64)
+    }
+
+    pub fn read_bit(&mut self) -> io::Result<bool> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    /// Number of bits read so far.
+    pub fn bits_read(&self) -> u64 {
+        self.bits_read
+    }
+
+    /// Drops the rest of the current byte, so the next read starts on a byte
+    /// boundary.
+    pub fn align_to_byte(&mut self) {
+        let skip = self.buffered % 8;
+        self.buffered -= skip;
+        self.bits_read += skip as u64;
+        match self.order {
+            BitOrder::MsbFirst => self.buffer &= mask(self.buffered),
+            BitOrder::LsbFirst => self.buffer >>= skip,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Yields single bits until the input ends or fails.
+impl<R: Read> Iterator for BitReader<R> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        self.read_bit().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn fields() -> impl Strategy<Value = Vec<(u64, u32)>> {
+        prop::collection::vec(
+            (0u32..=64).prop_flat_map(|count| (any::<u64>(), Just(count))),
+            0..200,
+        )
+    }
+
+    fn order() -> impl Strategy<Value = BitOrder> {
+        prop_oneof![Just(BitOrder::MsbFirst), Just(BitOrder::LsbFirst)]
+    }
+
+    proptest! {
+        #[test]
+        fn bit_fields_should_round_trip(fields in fields(), order in order()) {
+            let mut writer = BitWriter::with_order(Vec::new(), order);
+            for (value, count) in &fields {
+                writer.write_bits(*value, *count).unwrap();
+            }
+            let total: u64 = fields.iter().map(|(_, count)| *count as u64).sum();
+            prop_assert_eq!(writer.bits_written(), total);
+            let bytes = writer.finish().unwrap();
+            prop_assert_eq!(bytes.len() as u64, (total + 7) / 8);
+
+            let mut reader = BitReader::with_order(bytes.as_slice(), order);
+            for (value, count) in &fields {
+                let expected = if *count == 64 { *value } else { value & ((1 << count) - 1) };
+                prop_assert_eq!(reader.read_bits(*count).unwrap(), expected);
+            }
+        }
+
+        #[test]
+        fn single_bits_should_match_bit_fields(bytes in prop::collection::vec(any::<u8>(), 0..64)) {
+            let fields: Vec<u64> = BitReader::new(bytes.as_slice())
+                .collect::<Vec<bool>>()
+                .chunks(8)
+                .map(|bits| bits.iter().fold(0, |byte, bit| (byte << 1) | *bit as u64))
+                .collect();
+            let expected: Vec<u64> = bytes.iter().map(|b| *b as u64).collect();
+            prop_assert_eq!(fields, expected);
+        }
+    }
+
+    #[test]
+    fn lsb_first_should_pack_like_deflate() {
+        // A DEFLATE block header: BFINAL = 1, BTYPE = 01, then 5 bits 10011.
+        let mut writer = BitWriter::with_order(Vec::new(), BitOrder::LsbFirst);
+        writer.write_bits(1, 1).unwrap();
+        writer.write_bits(0b01, 2).unwrap();
+        writer.write_bits(0b10011, 5).unwrap();
+        assert_eq!(writer.finish().unwrap(), [0b1001_1011]);
+    }
+
+    #[test]
+    fn reading_past_the_end_should_fail() {
+        let mut reader = BitReader::new(&[0xff][..]);
+        assert_eq!(reader.read_bits(4).unwrap(), 0xf);
+        assert_eq!(
+            reader.read_bits(5).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        assert_eq!(reader.read_bits(4).unwrap(), 0xf);
+    }
+}
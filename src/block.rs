@@ -0,0 +1,552 @@
+use serde_json::{json, Map, Value};
+
+use crate::crc::Crc32;
+use crate::prelude::*;
+use crate::{bytes_to_u64, compress_member, CodecError, CompressOptions, HuffmanDecoder};
+
+/// Block size used by the CLI when `--block-size` is given without a value.
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+/// Largest block, and largest input [`compress`](crate::compress) codes as a
+/// single member. Symbol weights are `u32` and every token is at least one
+/// byte, so no count can overflow.
+pub const MAX_BLOCK_SIZE: usize = u32::MAX as usize;
+
+/// Where every block of a blocked file starts.
+///
+/// A blocked file is a sequence of members, each one a complete `.huf` file
+/// for one block of the input with its own table and a [`BlockCheck`],
+/// followed by the index as a member with an `"index"` header and no
+/// payload, and a last line holding the byte offset of that index member.
+/// Files without blocks are a single member and decode as before.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BlockIndex {
+    /// `(uncompressed offset, compressed offset)` of every block, in bytes.
+    /// Blocks start on a byte boundary, so no bit offset is needed.
+    pub blocks: Vec<(u64, u64)>,
+    /// Uncompressed size of the whole file.
+    pub size: u64,
+}
+
+impl BlockIndex {
+    pub(crate) fn is_index(mappings: &Value) -> bool {
+        mappings.get("index").is_some()
+    }
+
+    fn to_json(&self) -> Value {
+        json!({ "index": self.blocks, "size": self.size })
+    }
+
+    pub(crate) fn from_json(mappings: &Value) -> Result<Self, CodecError> {
+        let malformed = || CodecError::InvalidHeader("malformed block index");
+        let blocks = mappings["index"]
+            .as_array()
+            .ok_or_else(malformed)?
+            .iter()
+            .map(|entry| match entry.as_array().map(Vec::as_slice) {
+                Some([offset, position]) => Some((offset.as_u64()?, position.as_u64()?)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(malformed)?;
+        let size = mappings["size"].as_u64().ok_or_else(malformed)?;
+        Ok(Self { blocks, size })
+    }
+
+    /// Finds the index through the offset on the last line of `bytes`.
+    /// Returns `None` for files without blocks.
+    pub fn read(bytes: &[u8]) -> Result<Option<Self>, CodecError> {
+        let Some((b'\n', rest)) = bytes.split_last() else {
+            return Ok(None);
+        };
+        let start = rest.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        let digits = &rest[start..];
+        if digits.is_empty()
+            || !digits.iter().all(u8::is_ascii_digit)
+            || !rest[..start].ends_with(b"\n\n")
+        {
+            return Ok(None);
+        }
+        let position = core::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| digits.parse::<usize>().ok())
+            .filter(|position| *position < start);
+        let Some(position) = position else {
+            return Ok(None);
+        };
+        match HuffmanDecoder::new(&bytes[position..start]).read_mappings() {
+            Ok((mappings, _, 0)) if Self::is_index(&mappings) => {
+                Ok(Some(Self::from_json(&mappings)?))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The uncompressed offset and checksum of a block, in the mappings of every
+/// member written by [`compress`](crate::compress). Decoding checks the CRC, and the member headers
+/// double as sync markers: [`recover`](crate::recover::recover) finds the
+/// next intact block after damage by them and places it by its offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockCheck {
+    pub offset: u64,
+    /// CRC-32 of the offset as 8 little-endian bytes followed by the block
+    /// data, so that a changed offset fails the check too.
+    pub crc32: u32,
+}
+
+impl BlockCheck {
+    pub fn new(offset: u64, data: &[u8]) -> Self {
+        let mut crc = Crc32::new();
+        crc.update(&offset.to_le_bytes());
+        crc.update(data);
+        Self {
+            offset,
+            crc32: crc.finish(),
+        }
+    }
+
+    pub fn verify(&self, data: &[u8]) -> Result<(), CodecError> {
+        match Self::new(self.offset, data) == *self {
+            true => Ok(()),
+            false => Err(CodecError::Checksum),
+        }
+    }
+
+    pub(crate) fn insert_into(&self, mappings: &mut Map<String, Value>) {
+        mappings.insert("offset".into(), self.offset.into());
+        mappings.insert("crc32".into(), self.crc32.into());
+    }
+
+    /// `None` for members without a check, written by
+    /// [`compress_coded`](crate::compress_coded) or before checks existed.
+    /// Only one of the two fields is damage, not a missing check.
+    pub(crate) fn from_json(mappings: &Value) -> Result<Option<Self>, CodecError> {
+        let malformed = || CodecError::InvalidHeader("malformed block check");
+        let (offset, crc32) = match (mappings.get("offset"), mappings.get("crc32")) {
+            (Some(offset), Some(crc32)) => (offset, crc32),
+            (None, None) => return Ok(None),
+            _ => return Err(malformed()),
+        };
+        Ok(Some(Self {
+            offset: offset.as_u64().ok_or_else(malformed)?,
+            crc32: crc32
+                .as_u64()
+                .and_then(|crc32| u32::try_from(crc32).ok())
+                .ok_or_else(malformed)?,
+        }))
+    }
+}
+
+pub(crate) fn compress_blocks(
+    input: &[u8],
+    options: &CompressOptions,
+    block_size: usize,
+) -> Result<Vec<u8>, CodecError> {
+    let mut encoder = BlockEncoder::with_block_size(options, block_size);
+    let mut compressed = Vec::new();
+    encoder.compress_ready(input, &mut compressed, true)?;
+    encoder.finish(&mut compressed)?;
+    Ok(compressed)
+}
+
+/// Blocked compression of input that arrives in pieces. Every block is
+/// compressed as soon as the input after it shows where it has to end, so
+/// only the incomplete last block is buffered. The output is the same as
+/// [`compress`](crate::compress) with the same block size.
+#[derive(Debug, Clone)]
+pub struct BlockEncoder {
+    options: CompressOptions,
+    block_size: usize,
+    pending: Vec<u8>,
+    index: BlockIndex,
+    written: u64,
+}
+
+impl BlockEncoder {
+    /// Uses `options.block_size`, or [`DEFAULT_BLOCK_SIZE`] when it is not set.
+    pub fn new(options: &CompressOptions) -> Self {
+        Self::with_block_size(options, options.block_size.unwrap_or(DEFAULT_BLOCK_SIZE))
+    }
+
+    fn with_block_size(options: &CompressOptions, block_size: usize) -> Self {
+        Self {
+            options: options.clone(),
+            block_size: block_size.clamp(1, MAX_BLOCK_SIZE),
+            pending: Vec::new(),
+            index: BlockIndex::default(),
+            written: 0,
+        }
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Adds `input` and appends the members of the blocks it completes to
+    /// `output`.
+    pub fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), CodecError> {
+        if self.pending.is_empty() {
+            let consumed = self.compress_ready(input, output, false)?;
+            self.pending.extend_from_slice(&input[consumed..]);
+        } else {
+            let mut pending = core::mem::take(&mut self.pending);
+            pending.extend_from_slice(input);
+            let consumed = self.compress_ready(&pending, output, false)?;
+            pending.drain(..consumed);
+            self.pending = pending;
+        }
+        Ok(())
+    }
+
+    /// Compresses the rest of the input and appends it to `output`, followed
+    /// by the block index.
+    pub fn finish(mut self, output: &mut Vec<u8>) -> Result<(), CodecError> {
+        let pending = core::mem::take(&mut self.pending);
+        self.compress_ready(&pending, output, true)?;
+
+        let mappings = serde_json::to_string(&self.index.to_json()).unwrap();
+        output.extend(format!("0\n{}\n\n{}\n", mappings, self.written).into_bytes());
+        Ok(())
+    }
+
+    /// Compresses the blocks at the start of `input` whose end is known,
+    /// all of them when `last` is set, and returns the bytes consumed.
+    fn compress_ready(
+        &mut self,
+        input: &[u8],
+        output: &mut Vec<u8>,
+        last: bool,
+    ) -> Result<usize, CodecError> {
+        let mut start = 0;
+        while start < input.len() {
+            let tokenizer = self.options.tokenizer;
+            let mut end = start + self.block_size;
+            if end < input.len() {
+                end = tokenizer.boundary(input, end);
+                if end <= start {
+                    // The block is shorter than a token, end it after the token.
+                    end = (start + self.block_size..input.len())
+                        .find(|at| tokenizer.boundary(input, *at) == *at)
+                        .unwrap_or(input.len());
+                }
+            }
+            if end >= input.len() {
+                if !last {
+                    break;
+                }
+                end = input.len();
+            }
+            let block = &input[start..end];
+            let check = BlockCheck::new(self.index.size, block);
+            let member = compress_member(block, &self.options, Some(check))?;
+            self.index.blocks.push((self.index.size, self.written));
+            self.index.size += (end - start) as u64;
+            self.written += member.len() as u64;
+            output.extend_from_slice(&member);
+            start = end;
+        }
+        Ok(start)
+    }
+}
+
+/// Decoding of a `.huf` file that arrives in pieces, blocked or not. Each
+/// member is decoded once all of its bytes are in.
+#[derive(Debug, Clone, Default)]
+pub struct BlockDecoder {
+    pending: Vec<u8>,
+    /// Progress on the member at the start of `pending`.
+    scan: MemberScan,
+    members: u64,
+    done: bool,
+}
+
+impl BlockDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `input` and appends the data of the members it completes to
+    /// `output`.
+    pub fn update(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), CodecError> {
+        if self.done {
+            // Only the index offset follows the index.
+            return Ok(());
+        }
+        self.pending.extend_from_slice(input);
+        let mut start = 0;
+        while !self.done && start < self.pending.len() {
+            let rest = &self.pending[start..];
+            let Some(len) = self.scan.member_len(rest)? else {
+                break;
+            };
+            match HuffmanDecoder::new(&rest[..len]).decode_member()? {
+                Some((block, _)) => output.extend_from_slice(&block),
+                None => self.done = true,
+            }
+            self.members += 1;
+            self.scan = MemberScan::default();
+            start += len;
+        }
+        if self.done {
+            self.pending.clear();
+        } else {
+            self.pending.drain(..start);
+        }
+        Ok(())
+    }
+
+    /// Checks that the input did not stop in the middle of a member.
+    pub fn finish(self) -> Result<(), CodecError> {
+        if !self.pending.is_empty() {
+            return Err(CodecError::InvalidPayload);
+        }
+        if self.members == 0 {
+            return Err(CodecError::InvalidHeader("missing bit count"));
+        }
+        Ok(())
+    }
+}
+
+/// Search for the end of a member's header that picks up where the last
+/// call stopped, so feeding a large header in small pieces stays linear.
+#[derive(Debug, Clone, Default)]
+struct MemberScan {
+    /// Bytes searched so far without finding what comes next.
+    scanned: usize,
+    /// End of the bit count line and the bit count.
+    bits: Option<(usize, u64)>,
+    len: Option<usize>,
+}
+
+impl MemberScan {
+    /// Length of the member at the start of `bytes`, `None` while it is not
+    /// complete yet. `bytes` has to grow between calls, never change.
+    fn member_len(&mut self, bytes: &[u8]) -> Result<Option<usize>, CodecError> {
+        if self.len.is_none() {
+            self.len = self.header(bytes)?;
+        }
+        Ok(self.len.filter(|len| *len <= bytes.len()))
+    }
+
+    fn header(&mut self, bytes: &[u8]) -> Result<Option<usize>, CodecError> {
+        let (line, bits) = match self.bits {
+            Some(bits) => bits,
+            None => {
+                let new = &bytes[self.scanned..];
+                let Some(at) = new.iter().position(|b| *b == b'\n') else {
+                    if !new.iter().all(u8::is_ascii_digit) {
+                        return Err(CodecError::InvalidHeader("bit count is not numeric"));
+                    }
+                    self.scanned = bytes.len();
+                    return Ok(None);
+                };
+                let line = self.scanned + at;
+                let bits = (line, bytes_to_u64(&bytes[..line])?);
+                self.bits = Some(bits);
+                self.scanned = line + 1;
+                bits
+            }
+        };
+        // One byte back, a `\n\n` may straddle the previous end.
+        let from = self.scanned.saturating_sub(1).max(line + 1);
+        let Some(at) = bytes[from..].windows(2).position(|w| w == b"\n\n") else {
+            self.scanned = bytes.len();
+            return Ok(None);
+        };
+        let len = usize::try_from(bits.div_ceil(8))
+            .ok()
+            .and_then(|payload| payload.checked_add(from + at + 2))
+            .ok_or(CodecError::InvalidHeader("bit count is too large"))?;
+        Ok(Some(len))
+    }
+}
+
+pub(crate) fn read_range(bytes: &[u8], offset: u64, len: u64) -> Result<Vec<u8>, CodecError> {
+    let end = offset.saturating_add(len);
+    let Some(index) = BlockIndex::read(bytes)? else {
+        let decoded = HuffmanDecoder::new(bytes).decode()?;
+        let clamp = |i: u64| (i.min(decoded.len() as u64)) as usize;
+        return Ok(decoded[clamp(offset)..clamp(end)].to_vec());
+    };
+
+    let first = index
+        .blocks
+        .partition_point(|(start, _)| *start <= offset)
+        .saturating_sub(1);
+    let Some((first_start, _)) = index.blocks.get(first) else {
+        return Ok(Vec::new());
+    };
+    let mut decoded = Vec::new();
+    for (_, position) in index.blocks[first..]
+        .iter()
+        .take_while(|(start, _)| *start < end)
+    {
+        let member = usize::try_from(*position)
+            .ok()
+            .and_then(|position| bytes.get(position..))
+            .ok_or(CodecError::InvalidHeader("block index points past the end"))?;
+        let (block, _) = HuffmanDecoder::new(member)
+            .decode_member()?
+            .ok_or(CodecError::InvalidHeader("block index points at the index"))?;
+        decoded.extend_from_slice(&block);
+    }
+    let clamp = |i: u64| (i.saturating_sub(*first_start).min(decoded.len() as u64)) as usize;
+    Ok(decoded[clamp(offset)..clamp(end)].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, decompress, decompress_range, Tokenizer};
+
+    fn blocked(tokenizer: Tokenizer, block_size: usize) -> CompressOptions {
+        CompressOptions {
+            tokenizer,
+            block_size: Some(block_size),
+            ..CompressOptions::default()
+        }
+    }
+
+    #[test]
+    fn blocks_should_not_split_characters() {
+        let input = "héllo wörld, ünïcödé ".repeat(40);
+        let compressed = compress(input.as_bytes(), &blocked(Tokenizer::Chars, 64)).unwrap();
+        let index = BlockIndex::read(&compressed).unwrap().unwrap();
+
+        assert!(index.blocks.len() > 10);
+        assert_eq!(index.size, input.len() as u64);
+        for (offset, _) in &index.blocks {
+            assert!(input.is_char_boundary(*offset as usize));
+        }
+        assert_eq!(decompress(&compressed).unwrap(), input.as_bytes());
+    }
+
+    #[test]
+    fn blocks_shorter_than_a_token_should_end_after_it() {
+        let input = "𐀀a𐀀";
+        let compressed = compress(input.as_bytes(), &blocked(Tokenizer::Chars, 1)).unwrap();
+        let index = BlockIndex::read(&compressed).unwrap().unwrap();
+
+        let offsets: Vec<u64> = index.blocks.iter().map(|(offset, _)| *offset).collect();
+        assert_eq!(offsets, [0, 4, 5]);
+        assert_eq!(decompress(&compressed).unwrap(), input.as_bytes());
+    }
+
+    #[test]
+    fn ranges_should_match_slices_of_the_input() {
+        let input: Vec<u8> = (0..5000u32).map(|i| (i * i % 251) as u8).collect();
+        let blocked = compress(&input, &blocked(Tokenizer::Bytes, 700)).unwrap();
+        let whole = compress(
+            &input,
+            &CompressOptions {
+                tokenizer: Tokenizer::Bytes,
+                ..CompressOptions::default()
+            },
+        )
+        .unwrap();
+
+        for (offset, len) in [
+            (0, 10),
+            (650, 100),
+            (699, 2),
+            (1400, 0),
+            (4990, 50),
+            (6000, 5),
+        ] {
+            let start = (offset as usize).min(input.len());
+            let end = ((offset + len) as usize).min(input.len());
+            assert_eq!(
+                decompress_range(&blocked, offset, len).unwrap(),
+                &input[start..end]
+            );
+            assert_eq!(
+                decompress_range(&whole, offset, len).unwrap(),
+                &input[start..end]
+            );
+        }
+    }
+
+    #[test]
+    fn streaming_should_match_whole_input_for_any_chunking() {
+        let input = "zwölf Boxkämpfer jagen Viktor quer über den großen Sylter Deich. ".repeat(30);
+        let options = blocked(Tokenizer::Chars, 100);
+        let expected = compress(input.as_bytes(), &options).unwrap();
+
+        for chunk in [1, 7, 100, 101, 5000] {
+            let mut encoder = BlockEncoder::new(&options);
+            let mut compressed = Vec::new();
+            for piece in input.as_bytes().chunks(chunk) {
+                encoder.update(piece, &mut compressed).unwrap();
+            }
+            encoder.finish(&mut compressed).unwrap();
+            assert_eq!(compressed, expected);
+
+            let mut decoder = BlockDecoder::new();
+            let mut decoded = Vec::new();
+            for piece in compressed.chunks(chunk) {
+                decoder.update(piece, &mut decoded).unwrap();
+            }
+            decoder.finish().unwrap();
+            assert_eq!(decoded, input.as_bytes());
+        }
+    }
+
+    #[test]
+    fn member_scan_should_resume_where_it_stopped() {
+        let options = CompressOptions {
+            tokenizer: Tokenizer::Words { min_count: 1 },
+            ..CompressOptions::default()
+        };
+        let member = crate::compress_coded(&std::fs::read("small.txt").unwrap(), &options).unwrap();
+        let mut scan = MemberScan::default();
+        for end in 1..member.len() {
+            assert_eq!(scan.member_len(&member[..end]).unwrap(), None);
+            // The next call searches on from here.
+            assert!(scan.len.is_some() || scan.scanned == end);
+        }
+        assert_eq!(scan.member_len(&member).unwrap(), Some(member.len()));
+        assert_eq!(
+            MemberScan::default().member_len(&member).unwrap(),
+            Some(member.len())
+        );
+    }
+
+    #[test]
+    fn truncated_stream_should_fail_on_finish() {
+        let compressed = compress(b"abracadabra", &CompressOptions::default()).unwrap();
+        let mut decoder = BlockDecoder::new();
+        let mut decoded = Vec::new();
+        decoder
+            .update(&compressed[..compressed.len() - 1], &mut decoded)
+            .unwrap();
+        assert!(decoded.is_empty());
+        assert!(decoder.finish().is_err());
+    }
+
+    #[test]
+    fn only_incompressible_blocks_should_be_stored() {
+        let text = &std::fs::read("huffman.txt").unwrap()[..4000];
+        let input = [text, &crate::tests::noise(4000), text].concat();
+        let compressed = compress(&input, &blocked(Tokenizer::Bytes, 4000)).unwrap();
+
+        let stored = compressed
+            .windows(b"\"stored\":true".len())
+            .filter(|w| w == b"\"stored\":true")
+            .count();
+        assert_eq!(stored, 1);
+        assert!(compressed.len() < input.len());
+        assert_eq!(decompress(&compressed).unwrap(), input);
+        assert_eq!(
+            decompress_range(&compressed, 3500, 5000).unwrap(),
+            &input[3500..8500]
+        );
+    }
+
+    #[test]
+    fn empty_input_should_only_hold_the_index() {
+        let compressed = compress(b"", &blocked(Tokenizer::Chars, 64)).unwrap();
+        assert_eq!(compressed, b"0\n{\"index\":[],\"size\":0}\n\n0\n");
+        assert_eq!(decompress(&compressed).unwrap(), b"");
+    }
+}
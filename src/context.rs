@@ -0,0 +1,263 @@
+use alloc::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::io::{self, Write};
+use crate::prelude::*;
+use crate::tree::{canonical_codes, Code, HuffTree};
+use crate::{BitWriter, CodecError};
+
+/// Canonical code used for the symbol that follows one particular context
+/// symbol. Only code lengths are kept, the codes themselves are rebuilt with
+/// [`canonical_codes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextTable {
+    /// Symbols seen after the context, sorted.
+    pub symbols: Vec<u32>,
+    /// Code length of each entry in `symbols`. A context that is always
+    /// followed by the same symbol has a single entry of length zero, so that
+    /// symbol costs no bits at all.
+    pub lengths: Vec<u8>,
+}
+
+impl ContextTable {
+    fn from_counts(successors: &BTreeMap<u32, u32>) -> Self {
+        let symbols: Vec<u32> = successors.keys().copied().collect();
+        let weights: Vec<u32> = successors.values().copied().collect();
+        let lengths = match HuffTree::from_weights(&weights) {
+            Some(tree) => tree.code_lengths(),
+            None => vec![0],
+        };
+        Self { symbols, lengths }
+    }
+
+    /// `[context, symbol, length, symbol, length, ...]`
+    fn to_json(&self, context: u32) -> Value {
+        let mut entry = vec![context];
+        for (symbol, length) in self.symbols.iter().zip(&self.lengths) {
+            entry.push(*symbol);
+            entry.push(*length as u32);
+        }
+        entry.into()
+    }
+}
+
+/// Most symbols a model may code per payload bit, counting one bit more than
+/// the payload has. Contexts with a single successor code it in zero bits, so
+/// without a limit a few bytes could claim any number of symbols.
+pub const MAX_SYMBOLS_PER_BIT: u64 = 64;
+
+/// Order-1 model: one Huffman code per preceding symbol. Contexts without a
+/// table of their own, and the very first symbol, use the order-0 code built
+/// from the token weights.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ContextModel {
+    /// Number of coded symbols. Zero-length codes make the bit count useless
+    /// for telling where the payload ends.
+    pub symbol_count: u64,
+    pub tables: BTreeMap<u32, ContextTable>,
+}
+
+enum ContextDecoder {
+    Single(u32),
+    Tree(HuffTree, Vec<u32>),
+}
+
+impl ContextModel {
+    /// Gives a context its own table only when the bits it saves over the
+    /// order-0 code (`fallback_lengths`) outweigh the bytes the table adds to
+    /// the header, so rare contexts fall back to the order-0 code.
+    pub fn build(symbols: &[u32], fallback_lengths: &[u8]) -> Self {
+        let mut successors: BTreeMap<u32, BTreeMap<u32, u32>> = BTreeMap::new();
+        for pair in symbols.windows(2) {
+            *successors
+                .entry(pair[0])
+                .or_default()
+                .entry(pair[1])
+                .or_insert(0) += 1;
+        }
+
+        let mut tables = BTreeMap::new();
+        for (context, next) in successors {
+            let table = ContextTable::from_counts(&next);
+            let fallback_bits: u64 = next
+                .iter()
+                .map(|(symbol, count)| *count as u64 * fallback_lengths[*symbol as usize] as u64)
+                .sum();
+            let context_bits: u64 = next
+                .values()
+                .zip(&table.lengths)
+                .map(|(count, length)| *count as u64 * *length as u64)
+                .sum();
+            let header_bits = 8 * (table.to_json(context).to_string().len() as u64 + 1);
+            if context_bits + header_bits < fallback_bits {
+                tables.insert(context, table);
+            }
+        }
+
+        Self {
+            symbol_count: symbols.len() as u64,
+            tables,
+        }
+    }
+
+    /// Whether `bits` payload bits are enough for
+    /// [`symbol_count`](Self::symbol_count), see [`MAX_SYMBOLS_PER_BIT`].
+    pub fn backed_by(&self, bits: u64) -> bool {
+        self.symbol_count <= bits.saturating_add(1).saturating_mul(MAX_SYMBOLS_PER_BIT)
+    }
+
+    pub(crate) fn encode<W: Write>(
+        &self,
+        symbols: &[u32],
+        fallback: &[Code],
+        bit_writer: &mut BitWriter<W>,
+    ) -> io::Result<()> {
+        let codes: BTreeMap<u32, Vec<Code>> = self
+            .tables
+            .iter()
+            .map(|(context, table)| (*context, canonical_codes(&table.lengths)))
+            .collect();
+
+        let mut previous = None;
+        for symbol in symbols {
+            let code = match previous.and_then(|context| self.tables.get(&context)) {
+                Some(table) => {
+                    let index = table.symbols.binary_search(symbol).unwrap();
+                    &codes[&previous.unwrap()][index]
+                }
+                None => &fallback[*symbol as usize],
+            };
+            bit_writer.write_bits(code.value, code.len as u32)?;
+            previous = Some(*symbol);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn decode(
+        &self,
+        mut bits: impl Iterator<Item = bool>,
+        fallback: &HuffTree,
+    ) -> Result<Vec<u32>, CodecError> {
+        let mut decoders = BTreeMap::new();
+        for (context, context_table) in &self.tables {
+            let decoder = match context_table.symbols.as_slice() {
+                [symbol] => ContextDecoder::Single(*symbol),
+                _ => ContextDecoder::Tree(
+                    HuffTree::from_code_lengths(&context_table.lengths).ok_or(
+                        CodecError::InvalidHeader("context code is not a prefix code"),
+                    )?,
+                    context_table.symbols.clone(),
+                ),
+            };
+            decoders.insert(*context, decoder);
+        }
+
+        let mut decoded = Vec::new();
+        let mut previous = None;
+        for _ in 0..self.symbol_count {
+            let symbol = match previous.and_then(|context| decoders.get(&context)) {
+                Some(ContextDecoder::Single(symbol)) => *symbol,
+                Some(ContextDecoder::Tree(tree, symbols)) => {
+                    symbols[tree
+                        .decode_symbol(&mut bits)
+                        .ok_or(CodecError::InvalidPayload)? as usize]
+                }
+                None => fallback
+                    .decode_symbol(&mut bits)
+                    .ok_or(CodecError::InvalidPayload)?,
+            };
+            decoded.push(symbol);
+            previous = Some(symbol);
+        }
+        Ok(decoded)
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        self.tables
+            .iter()
+            .map(|(context, table)| table.to_json(*context))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    pub(crate) fn from_json(
+        symbol_count: u64,
+        contexts: &Value,
+        alphabet_size: usize,
+    ) -> Result<Self, CodecError> {
+        let malformed = || CodecError::InvalidHeader("malformed context table");
+        let mut tables = BTreeMap::new();
+        for entry in contexts
+            .as_array()
+            .ok_or(CodecError::InvalidHeader("missing contexts"))?
+        {
+            let numbers = entry
+                .as_array()
+                .and_then(|entry| {
+                    entry
+                        .iter()
+                        .map(|n| n.as_u64().and_then(|n| u32::try_from(n).ok()))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(malformed)?;
+            let (context, pairs) = match numbers.split_first() {
+                Some((context, pairs)) if !pairs.is_empty() && pairs.len() % 2 == 0 => {
+                    (*context, pairs)
+                }
+                _ => return Err(malformed()),
+            };
+            let mut table = ContextTable {
+                symbols: Vec::with_capacity(pairs.len() / 2),
+                lengths: Vec::with_capacity(pairs.len() / 2),
+            };
+            for pair in pairs.chunks(2) {
+                if pair[0] as usize >= alphabet_size || pair[1] > 64 {
+                    return Err(malformed());
+                }
+                table.symbols.push(pair[0]);
+                table.lengths.push(pair[1] as u8);
+            }
+            if context as usize >= alphabet_size {
+                return Err(malformed());
+            }
+            tables.insert(context, table);
+        }
+        Ok(Self {
+            symbol_count,
+            tables,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_with_one_successor_should_cost_no_bits() {
+        // "qu" repeated: after q always comes u and the other way around.
+        let symbols: Vec<u32> = (0..200).map(|i| i % 2).collect();
+        let model = ContextModel::build(&symbols, &[1, 1]);
+
+        assert_eq!(model.tables[&0].lengths, [0]);
+        assert_eq!(model.tables[&1].symbols, [0]);
+
+        let mut bit_writer = BitWriter::new(Vec::new());
+        model
+            .encode(
+                &symbols,
+                &[Code { value: 0, len: 1 }, Code { value: 1, len: 1 }],
+                &mut bit_writer,
+            )
+            .unwrap();
+        assert_eq!(bit_writer.bits_written(), 1);
+    }
+
+    #[test]
+    fn rare_contexts_should_fall_back_to_order_0() {
+        let symbols = [0, 1, 2, 0, 2, 1];
+        let model = ContextModel::build(&symbols, &[1, 2, 2]);
+        assert!(model.tables.is_empty());
+    }
+}
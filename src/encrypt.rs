@@ -0,0 +1,286 @@
+//! Password-based encryption of `.huf` files, with the `encrypt` feature.
+//!
+//! An encrypted file is a single member whose mappings hold the cipher, the
+//! Argon2id parameters, the salt and the nonce instead of a code table, and
+//! whose payload is the whole compressed file sealed with ChaCha20-Poly1305.
+//! The member header is authenticated along with the payload, so a wrong
+//! password or any change to the salt, the parameters or the payload fails
+//! with [`CodecError::Authentication`].
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde_json::{json, Value};
+
+use crate::prelude::*;
+
+use crate::{is_encrypted_mappings, token_from_json, CodecError, HuffmanDecoder};
+
+const CIPHER: &str = "chacha20poly1305";
+const KDF: &str = "argon2id";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+/// Highest Argon2 memory cost read from a header, 1 GiB, so that a forged
+/// header cannot make decryption allocate without bound.
+const MAX_M_COST: u32 = 1 << 20;
+/// Highest number of passes and lanes read from a header, for the same
+/// reason: the header is only authenticated after the key is derived.
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 16;
+
+/// Argon2id cost parameters, stored in the header of every encrypted file.
+/// At most 1 GiB, 16 passes and 16 lanes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory in KiB.
+    pub m_cost: u32,
+    /// Passes over the memory.
+    pub t_cost: u32,
+    /// Lanes.
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    /// The `argon2` crate defaults: 19 MiB, 2 passes, 1 lane.
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    fn derive_key(&self, password: &[u8], salt: &[u8]) -> Result<Key, CodecError> {
+        let invalid = |_| CodecError::InvalidHeader("invalid key derivation parameters");
+        let params =
+            Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32)).map_err(invalid)?;
+        let mut key = Key::default();
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password, salt, &mut key)
+            .map_err(invalid)?;
+        Ok(key)
+    }
+
+    fn from_json(mappings: &Value) -> Result<Self, CodecError> {
+        let cost = |name: &str| {
+            mappings[name]
+                .as_u64()
+                .and_then(|cost| u32::try_from(cost).ok())
+                .ok_or(CodecError::InvalidHeader(
+                    "malformed key derivation parameters",
+                ))
+        };
+        Self {
+            m_cost: cost("m_cost")?,
+            t_cost: cost("t_cost")?,
+            p_cost: cost("p_cost")?,
+        }
+        .checked()
+    }
+
+    /// `self` if [`decrypt`] would accept it.
+    fn checked(self) -> Result<Self, CodecError> {
+        if self.m_cost > MAX_M_COST {
+            return Err(CodecError::InvalidHeader(
+                "key derivation memory cost is too high",
+            ));
+        }
+        if self.t_cost > MAX_T_COST || self.p_cost > MAX_P_COST {
+            return Err(CodecError::InvalidHeader(
+                "key derivation time cost or lanes are too high",
+            ));
+        }
+        Ok(self)
+    }
+}
+
+/// Whether `bytes` start with the header of an encrypted file.
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    HuffmanDecoder::new(bytes)
+        .read_mappings()
+        .is_ok_and(|(mappings, _, _)| is_encrypted_mappings(&mappings))
+}
+
+/// Encrypts the `.huf` file `compressed` with a key derived from `password`
+/// with the default [`KdfParams`].
+pub fn encrypt(compressed: &[u8], password: &[u8]) -> Result<Vec<u8>, CodecError> {
+    encrypt_with(compressed, password, &KdfParams::default())
+}
+
+/// [`encrypt`] with a fresh random salt and nonce and the given parameters,
+/// which must stay within the limits [`decrypt`] accepts.
+pub fn encrypt_with(
+    compressed: &[u8],
+    password: &[u8],
+    params: &KdfParams,
+) -> Result<Vec<u8>, CodecError> {
+    let params = params.checked()?;
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let key = params.derive_key(password, &salt)?;
+
+    // Poly1305 appends a 16 byte tag.
+    let bits_count = (compressed.len() as u64 + 16) * 8;
+    let mappings = json!({
+        "encrypted": CIPHER,
+        "kdf": KDF,
+        "m_cost": params.m_cost,
+        "t_cost": params.t_cost,
+        "p_cost": params.p_cost,
+        "salt": salt.to_vec(),
+        "nonce": nonce.to_vec(),
+    });
+    let mut encrypted = format!("{}\n{}\n\n", bits_count, mappings).into_bytes();
+    let sealed = ChaCha20Poly1305::new(&key)
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: compressed,
+                aad: &encrypted,
+            },
+        )
+        .expect("ChaCha20-Poly1305 seals up to 256 GiB");
+    encrypted.extend_from_slice(&sealed);
+    Ok(encrypted)
+}
+
+/// Checks and decrypts a file written by [`encrypt`], returning the `.huf`
+/// file inside it.
+pub fn decrypt(bytes: &[u8], password: &[u8]) -> Result<Vec<u8>, CodecError> {
+    let (mappings, header_len, bits_count) = HuffmanDecoder::new(bytes).read_mappings()?;
+    if !is_encrypted_mappings(&mappings) {
+        return Err(CodecError::InvalidHeader("file is not encrypted"));
+    }
+    if mappings["encrypted"] != CIPHER || mappings["kdf"] != KDF {
+        return Err(CodecError::InvalidHeader("unknown cipher"));
+    }
+    let params = KdfParams::from_json(&mappings)?;
+    let salt = token_from_json(&mappings["salt"])
+        .filter(|salt| salt.len() == SALT_LEN)
+        .ok_or(CodecError::InvalidHeader("malformed salt"))?;
+    let nonce = token_from_json(&mappings["nonce"])
+        .filter(|nonce| nonce.len() == NONCE_LEN)
+        .ok_or(CodecError::InvalidHeader("malformed nonce"))?;
+
+    // A payload cut short or grown fails like a changed one.
+    let sealed = &bytes[header_len..];
+    if sealed.len() as u64 * 8 != bits_count {
+        return Err(CodecError::Authentication);
+    }
+    let key = params.derive_key(password, &salt)?;
+    ChaCha20Poly1305::new(&key)
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: sealed,
+                aad: &bytes[..header_len],
+            },
+        )
+        .map_err(|_| CodecError::Authentication)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, decompress, CompressOptions};
+
+    /// Cheap enough for unoptimized test builds.
+    const FAST: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn encrypted() -> (Vec<u8>, Vec<u8>) {
+        let input = std::fs::read("huffman.txt").unwrap()[..20_000].to_vec();
+        let options = CompressOptions {
+            block_size: Some(4096),
+            ..CompressOptions::default()
+        };
+        let compressed = compress(&input, &options).unwrap();
+        (input, encrypt_with(&compressed, b"hunter2", &FAST).unwrap())
+    }
+
+    #[test]
+    fn encrypted_files_should_round_trip() {
+        let (input, encrypted) = encrypted();
+
+        assert!(is_encrypted(&encrypted));
+        let compressed = decrypt(&encrypted, b"hunter2").unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), input);
+    }
+
+    #[test]
+    fn salt_and_nonce_should_differ_every_time() {
+        let (_, first) = encrypted();
+        let (_, second) = encrypted();
+        assert_ne!(first, second);
+    }
+
+    /// `encrypted` with its mappings changed by `change` and the bit count
+    /// line and payload left alone.
+    fn with_mappings(encrypted: &[u8], change: impl FnOnce(&mut Value)) -> Vec<u8> {
+        let (mut mappings, header_len, bits_count) =
+            HuffmanDecoder::new(encrypted).read_mappings().unwrap();
+        change(&mut mappings);
+        let mut tampered = format!("{}\n{}\n\n", bits_count, mappings).into_bytes();
+        tampered.extend_from_slice(&encrypted[header_len..]);
+        tampered
+    }
+
+    #[test]
+    fn wrong_password_and_tampering_should_fail_authentication() {
+        let (_, encrypted) = encrypted();
+        assert_eq!(with_mappings(&encrypted, |_| {}), encrypted);
+
+        let mut payload = encrypted.clone();
+        *payload.last_mut().unwrap() ^= 1;
+        let salt = with_mappings(&encrypted, |mappings| {
+            mappings["salt"][0] = json!(mappings["salt"][0].as_u64().unwrap() ^ 1);
+        });
+        let nonce = with_mappings(&encrypted, |mappings| {
+            mappings["nonce"][11] = json!(mappings["nonce"][11].as_u64().unwrap() ^ 1);
+        });
+        let t_cost = with_mappings(&encrypted, |mappings| mappings["t_cost"] = json!(2));
+        let truncated = &encrypted[..encrypted.len() - 1];
+        let mut grown = encrypted.clone();
+        grown.push(0);
+
+        assert!(matches!(
+            decrypt(&encrypted, b"hunter3"),
+            Err(CodecError::Authentication)
+        ));
+        for tampered in [&payload[..], &salt, &nonce, &t_cost, truncated, &grown] {
+            assert!(matches!(
+                decrypt(tampered, b"hunter2"),
+                Err(CodecError::Authentication)
+            ));
+        }
+    }
+
+    #[test]
+    fn decompress_should_ask_for_a_password() {
+        let (_, encrypted) = encrypted();
+        assert!(matches!(decompress(&encrypted), Err(CodecError::Encrypted)));
+    }
+
+    #[test]
+    fn forged_costs_should_be_rejected() {
+        let (_, encrypted) = encrypted();
+        for cost in ["m_cost", "t_cost", "p_cost"] {
+            let forged = with_mappings(&encrypted, |mappings| mappings[cost] = json!(u32::MAX));
+            assert!(
+                matches!(
+                    decrypt(&forged, b"hunter2"),
+                    Err(CodecError::InvalidHeader(_))
+                ),
+                "{cost}"
+            );
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! C interface, declared in `include/compressor.h`.
+//!
+//! Every function returns a [`HufStatus`]; output is handed over in a
+//! [`HufBuffer`] that the caller releases with [`huf_buffer_free`].
+
+use std::ffi::{c_char, c_int, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use crate::{compress, decompress, BlockDecoder, BlockEncoder, CodecError, CompressOptions};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HufStatus {
+    Ok = 0,
+    /// A required pointer was null or the context was already finished.
+    InvalidArgument = 1,
+    /// Unknown tokenizer name, or input that the tokenizer rejects.
+    Tokenizer = 2,
+    TooFewSymbols = 3,
+    InvalidHeader = 4,
+    InvalidPayload = 5,
+    Io = 6,
+    /// The library panicked; this is a bug.
+    Panic = 7,
+    /// The input is an encrypted `.huf` file, which the C interface cannot
+    /// decrypt.
+    Encrypted = 8,
+    Authentication = 9,
+    /// A block of the input does not match its checksum.
+    Checksum = 10,
+}
+
+impl From<&CodecError> for HufStatus {
+    fn from(err: &CodecError) -> Self {
+        match err {
+            CodecError::Tokenizer(_) => HufStatus::Tokenizer,
+            CodecError::TooFewSymbols => HufStatus::TooFewSymbols,
+            CodecError::InvalidHeader(_) => HufStatus::InvalidHeader,
+            CodecError::InvalidPayload => HufStatus::InvalidPayload,
+            CodecError::Io(_) => HufStatus::Io,
+            CodecError::Encrypted => HufStatus::Encrypted,
+            CodecError::Authentication => HufStatus::Authentication,
+            CodecError::Checksum => HufStatus::Checksum,
+        }
+    }
+}
+
+/// Bytes allocated by the library.
+#[repr(C)]
+pub struct HufBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl HufBuffer {
+    fn empty() -> Self {
+        Self {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        Self {
+            data: Box::into_raw(bytes.into_boxed_slice()) as *mut u8,
+            len,
+        }
+    }
+}
+
+/// Mirrors [`CompressOptions`].
+#[repr(C)]
+pub struct HufOptions {
+    /// Tokenizer name as accepted by `--tokenizer`, null for `chars`.
+    pub tokenizer: *const c_char,
+    pub order1: bool,
+    pub rle: bool,
+    /// Block size in bytes, 0 for no blocks (or the default block size for
+    /// a streaming encoder).
+    pub block_size: usize,
+}
+
+/// Streaming encoder handle.
+pub struct HufEncoder {
+    encoder: Option<BlockEncoder>,
+}
+
+/// Streaming decoder handle.
+pub struct HufDecoder {
+    decoder: Option<BlockDecoder>,
+}
+
+unsafe fn options_from_c(options: *const HufOptions) -> Result<CompressOptions, HufStatus> {
+    let Some(options) = options.as_ref() else {
+        return Ok(CompressOptions::default());
+    };
+    let tokenizer = match options.tokenizer.is_null() {
+        true => Default::default(),
+        false => CStr::from_ptr(options.tokenizer)
+            .to_str()
+            .ok()
+            .and_then(|name| name.parse().ok())
+            .ok_or(HufStatus::Tokenizer)?,
+    };
+    Ok(CompressOptions {
+        tokenizer,
+        order1: options.order1,
+        rle: options.rle,
+        block_size: (options.block_size > 0).then_some(options.block_size),
+    })
+}
+
+unsafe fn input<'a>(data: *const u8, len: usize) -> Result<&'a [u8], HufStatus> {
+    match (data.is_null(), len) {
+        (_, 0) => Ok(&[]),
+        (true, _) => Err(HufStatus::InvalidArgument),
+        (false, _) => Ok(slice::from_raw_parts(data, len)),
+    }
+}
+
+/// Runs `f` and stores its output in `out`, turning errors and panics into a
+/// status. A context that panicked may be left in any state, but panics are
+/// bugs and must not unwind into C.
+unsafe fn run(out: *mut HufBuffer, f: impl FnOnce() -> Result<Vec<u8>, HufStatus>) -> HufStatus {
+    let Some(out) = out.as_mut() else {
+        return HufStatus::InvalidArgument;
+    };
+    *out = HufBuffer::empty();
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(bytes)) => {
+            *out = HufBuffer::from_vec(bytes);
+            HufStatus::Ok
+        }
+        Ok(Err(status)) => status,
+        Err(_) => HufStatus::Panic,
+    }
+}
+
+/// Compresses `len` bytes at `input` into `out`. `options` may be null.
+///
+/// # Safety
+///
+/// `input` must point to `len` readable bytes, `options` must be null or
+/// valid, and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn huf_compress(
+    input: *const u8,
+    len: usize,
+    options: *const HufOptions,
+    out: *mut HufBuffer,
+) -> HufStatus {
+    run(out, || {
+        let options = options_from_c(options)?;
+        let input = self::input(input, len)?;
+        compress(input, &options).map_err(|e| HufStatus::from(&e))
+    })
+}
+
+/// Decompresses the `.huf` data at `input` into `out`.
+///
+/// # Safety
+///
+/// `input` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn huf_decompress(
+    input: *const u8,
+    len: usize,
+    out: *mut HufBuffer,
+) -> HufStatus {
+    run(out, || {
+        let input = self::input(input, len)?;
+        decompress(input).map_err(|e| HufStatus::from(&e))
+    })
+}
+
+/// Releases a buffer returned by the library. Null buffers are ignored.
+///
+/// # Safety
+///
+/// `buffer` must come from this library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn huf_buffer_free(buffer: HufBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+/// Creates a streaming encoder that writes blocked output; null if the
+/// options are invalid.
+///
+/// # Safety
+///
+/// `options` must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn huf_encoder_new(options: *const HufOptions) -> *mut HufEncoder {
+    match options_from_c(options) {
+        Ok(options) => Box::into_raw(Box::new(HufEncoder {
+            encoder: Some(BlockEncoder::new(&options)),
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Feeds `len` bytes to the encoder; `out` receives the blocks they complete,
+/// possibly none.
+///
+/// # Safety
+///
+/// `encoder` must come from [`huf_encoder_new`], `input` must point to `len`
+/// readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn huf_encoder_update(
+    encoder: *mut HufEncoder,
+    input: *const u8,
+    len: usize,
+    out: *mut HufBuffer,
+) -> HufStatus {
+    let Some(encoder) = encoder.as_mut().and_then(|e| e.encoder.as_mut()) else {
+        return HufStatus::InvalidArgument;
+    };
+    run(out, || {
+        let mut output = Vec::new();
+        let input = self::input(input, len)?;
+        encoder
+            .update(input, &mut output)
+            .map_err(|e| HufStatus::from(&e))?;
+        Ok(output)
+    })
+}
+
+/// Writes the last block and the block index to `out`. The encoder cannot be
+/// fed afterwards but still has to be freed.
+///
+/// # Safety
+///
+/// `encoder` must come from [`huf_encoder_new`] and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn huf_encoder_finish(
+    encoder: *mut HufEncoder,
+    out: *mut HufBuffer,
+) -> HufStatus {
+    // Checked before the encoder is taken, so a null `out` leaves it usable.
+    if out.is_null() {
+        return HufStatus::InvalidArgument;
+    }
+    let Some(encoder) = encoder.as_mut().and_then(|e| e.encoder.take()) else {
+        return HufStatus::InvalidArgument;
+    };
+    run(out, move || {
+        let mut output = Vec::new();
+        encoder
+            .finish(&mut output)
+            .map_err(|e| HufStatus::from(&e))?;
+        Ok(output)
+    })
+}
+
+/// # Safety
+///
+/// `encoder` must be null or come from [`huf_encoder_new`], and must not be
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn huf_encoder_free(encoder: *mut HufEncoder) {
+    if !encoder.is_null() {
+        drop(Box::from_raw(encoder));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn huf_decoder_new() -> *mut HufDecoder {
+    Box::into_raw(Box::new(HufDecoder {
+        decoder: Some(BlockDecoder::new()),
+    }))
+}
+
+/// Feeds `len` compressed bytes to the decoder; `out` receives the data of
+/// the blocks they complete, possibly none.
+///
+/// # Safety
+///
+/// `decoder` must come from [`huf_decoder_new`], `input` must point to `len`
+/// readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn huf_decoder_update(
+    decoder: *mut HufDecoder,
+    input: *const u8,
+    len: usize,
+    out: *mut HufBuffer,
+) -> HufStatus {
+    let Some(decoder) = decoder.as_mut().and_then(|d| d.decoder.as_mut()) else {
+        return HufStatus::InvalidArgument;
+    };
+    run(out, || {
+        let mut output = Vec::new();
+        let input = self::input(input, len)?;
+        decoder
+            .update(input, &mut output)
+            .map_err(|e| HufStatus::from(&e))?;
+        Ok(output)
+    })
+}
+
+/// Checks that the compressed data did not end in the middle of a block.
+///
+/// # Safety
+///
+/// `decoder` must come from [`huf_decoder_new`].
+#[no_mangle]
+pub unsafe extern "C" fn huf_decoder_finish(decoder: *mut HufDecoder) -> HufStatus {
+    match decoder.as_mut().and_then(|d| d.decoder.take()) {
+        Some(decoder) => match decoder.finish() {
+            Ok(()) => HufStatus::Ok,
+            Err(e) => HufStatus::from(&e),
+        },
+        None => HufStatus::InvalidArgument,
+    }
+}
+
+/// # Safety
+///
+/// `decoder` must be null or come from [`huf_decoder_new`], and must not be
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn huf_decoder_free(decoder: *mut HufDecoder) {
+    if !decoder.is_null() {
+        drop(Box::from_raw(decoder));
+    }
+}
+
+/// Static, NUL-terminated description of a status code. Takes a plain int
+/// so that C cannot hand in a value outside the enum.
+#[no_mangle]
+pub extern "C" fn huf_status_message(status: c_int) -> *const c_char {
+    let message: &'static [u8] = match status {
+        0 => b"ok\0",
+        1 => b"invalid argument\0",
+        2 => b"unknown tokenizer or input it cannot split\0",
+        3 => b"cannot build huffman for less than 2 unique symbols\0",
+        4 => b"invalid header\0",
+        5 => b"file is invalid\0",
+        6 => b"i/o error\0",
+        7 => b"internal error\0",
+        8 => b"file is encrypted\0",
+        9 => b"wrong password or tampered file\0",
+        10 => b"block checksum mismatch, the file is damaged\0",
+        _ => b"unknown status\0",
+    };
+    message.as_ptr() as *const c_char
+}
@@ -0,0 +1,79 @@
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+/// Contents of an input file, either read into memory or mapped.
+///
+/// Mapping skips the copy into a heap buffer: pages are loaded on demand and,
+/// being backed by the file, can be dropped again under memory pressure. The
+/// compressed or decompressed output is still built in memory, so mapping
+/// saves the input buffer and no more. Compress mapped input with
+/// [`CompressOptions::block_size`](crate::CompressOptions::block_size) so that
+/// only one block is tokenized at a time.
+pub enum Input {
+    Buffered(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Input {
+    /// Reads the whole file into memory.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        fs::read(path).map(Input::Buffered)
+    }
+
+    /// Maps the file. The file must not be changed by anyone while it is
+    /// mapped, the data would change under the codec. Empty files cannot be
+    /// mapped and are returned as an empty buffer.
+    #[cfg(feature = "mmap")]
+    pub fn map(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(Input::Buffered(Vec::new()));
+        }
+        // SAFETY: the mapping is read-only and the caller promises that the
+        // file is not modified while it is in use.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(Input::Mapped(map))
+    }
+
+    /// Maps the file when `mmap` is set and the feature is enabled, reads it
+    /// otherwise.
+    pub fn open(path: impl AsRef<Path>, mmap: bool) -> io::Result<Self> {
+        #[cfg(feature = "mmap")]
+        if mmap {
+            return Self::map(path);
+        }
+        let _ = mmap;
+        Self::read(path)
+    }
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Input::Buffered(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            Input::Mapped(map) => map,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_and_buffered_input_should_match() {
+        let buffered = Input::read("small.txt").unwrap();
+        let mapped = Input::open("small.txt", true).unwrap();
+
+        assert!(!buffered.is_empty());
+        assert_eq!(&*buffered, &*mapped);
+        #[cfg(feature = "mmap")]
+        assert!(matches!(mapped, Input::Mapped(_)));
+    }
+}
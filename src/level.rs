@@ -0,0 +1,157 @@
+//! Compression levels, see [`Level`].
+
+use core::fmt::{self, Display, Formatter};
+
+use log::debug;
+
+use crate::prelude::*;
+
+use crate::token::DEFAULT_MIN_WORD_COUNT;
+use crate::{compress, CodecError, CompressOptions, Tokenizer};
+
+const fn strategy(tokenizer: Tokenizer, order1: bool, rle: bool) -> CompressOptions {
+    CompressOptions {
+        tokenizer,
+        order1,
+        rle,
+        block_size: None,
+    }
+}
+
+const WORDS: Tokenizer = Tokenizer::Words {
+    min_count: DEFAULT_MIN_WORD_COUNT,
+};
+
+/// The coding options level `n` tries, the first `n` of them, cheapest first.
+/// Order-1 tables cost more time to build than order-0 ones and n-grams and
+/// words need bigger token tables than bytes and chars.
+pub const STRATEGIES: [CompressOptions; 9] = [
+    strategy(Tokenizer::Bytes, false, false),
+    strategy(Tokenizer::Chars, false, false),
+    strategy(WORDS, false, false),
+    strategy(Tokenizer::Bytes, false, true),
+    strategy(Tokenizer::NGrams(2), false, false),
+    strategy(Tokenizer::Chars, true, false),
+    strategy(WORDS, true, false),
+    strategy(Tokenizer::Bytes, true, true),
+    strategy(Tokenizer::NGrams(2), true, false),
+];
+
+/// A compression level from 1 (fastest) to 9 (smallest output).
+///
+/// Level `n` compresses the input with each of the first `n`
+/// [`STRATEGIES`] and keeps the smallest result, so a higher level never
+/// gives larger output, it only takes longer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Level(u8);
+
+impl Level {
+    pub const FASTEST: Level = Level(1);
+    pub const BEST: Level = Level(9);
+
+    /// `None` unless `level` is 1 to 9.
+    pub fn new(level: u8) -> Option<Self> {
+        (1..=9).contains(&level).then_some(Level(level))
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    pub fn strategies(self) -> &'static [CompressOptions] {
+        &STRATEGIES[..self.0 as usize]
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Compresses `input` with every strategy of `level`, blocked with
+/// `block_size` as in [`compress`], and returns the smallest output.
+/// Strategies that cannot code the input, chars on binary data, are skipped;
+/// the first error is returned when none can.
+pub fn compress_level(
+    input: &[u8],
+    level: Level,
+    block_size: Option<usize>,
+) -> Result<Vec<u8>, CodecError> {
+    let mut best: Option<Vec<u8>> = None;
+    let mut first_error = None;
+    for strategy in level.strategies() {
+        let options = CompressOptions {
+            block_size,
+            ..strategy.clone()
+        };
+        match compress(input, &options) {
+            Ok(compressed) => {
+                debug!("{:?}: {} bytes", strategy, compressed.len());
+                if best
+                    .as_ref()
+                    .map_or(true, |best| compressed.len() < best.len())
+                {
+                    best = Some(compressed);
+                }
+            }
+            Err(err) => {
+                debug!("{:?}: {}", strategy, err);
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    best.ok_or_else(|| first_error.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompress;
+
+    fn fixtures() -> Vec<Vec<u8>> {
+        let mut text = std::fs::read("huffman.txt").unwrap();
+        text.truncate(20_000);
+        let binary = (0..20_000u32)
+            .map(|i| {
+                if i % 7 == 0 {
+                    0xff
+                } else {
+                    (i % 251) as u8 / 64
+                }
+            })
+            .collect();
+        vec![std::fs::read("small.txt").unwrap(), text, binary]
+    }
+
+    #[test]
+    fn higher_levels_should_never_give_larger_output() {
+        for input in fixtures() {
+            let mut previous = usize::MAX;
+            for level in 1..=9 {
+                let compressed = compress_level(&input, Level::new(level).unwrap(), None).unwrap();
+                assert_eq!(decompress(&compressed).unwrap(), input);
+                assert!(compressed.len() <= previous, "level {level}");
+                previous = compressed.len();
+            }
+        }
+    }
+
+    #[test]
+    fn best_level_should_beat_fastest_on_text() {
+        let text = &fixtures()[1];
+        // Same blocks on both sides, so only the level differs.
+        for block_size in [None, Some(8192)] {
+            let fastest = compress_level(text, Level::FASTEST, block_size).unwrap();
+            let best = compress_level(text, Level::BEST, block_size).unwrap();
+            assert!(best.len() < fastest.len(), "{:?}", block_size);
+        }
+    }
+
+    #[test]
+    fn levels_should_be_one_to_nine() {
+        assert_eq!(Level::new(0), None);
+        assert_eq!(Level::new(10), None);
+        assert_eq!(Level::new(5).unwrap().strategies().len(), 5);
+    }
+}
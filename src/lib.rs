@@ -0,0 +1,1213 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use core::fmt::{self, Display, Formatter};
+
+use log::{debug, trace};
+use serde_json::{Map, Value};
+
+use crate::io::Write;
+use crate::prelude::*;
+
+pub use crate::bits::{BitOrder, BitReader, BitWriter};
+pub use crate::block::{BlockCheck, BlockDecoder, BlockEncoder, BlockIndex};
+
+pub use crate::context::ContextModel;
+#[cfg(feature = "fs")]
+pub use crate::input::Input;
+pub use crate::level::{compress_level, Level};
+pub use crate::recover::{recover, Damaged, Recovered};
+pub use crate::token::{TokenTable, Tokenizer, TokenizerError};
+pub use crate::tree::{Code, HuffTree};
+
+#[cfg(feature = "tokio")]
+pub mod async_io;
+pub mod bits;
+pub mod block;
+pub mod context;
+pub mod crc;
+#[cfg(feature = "encrypt")]
+pub mod encrypt;
+#[cfg(feature = "std")]
+pub mod ffi;
+#[cfg(feature = "fs")]
+pub mod input;
+pub mod io;
+pub mod level;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "std")]
+pub mod read;
+pub mod recover;
+pub mod rle;
+#[cfg(feature = "std")]
+pub mod stats;
+pub mod token;
+pub mod tree;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod write;
+
+/// The `alloc` types that are in the `std` prelude.
+mod prelude {
+    pub use alloc::borrow::ToOwned;
+    pub use alloc::boxed::Box;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec::Vec;
+    pub use alloc::{format, vec};
+}
+
+// thiserror needs `std` on our toolchain, so the error impls are written out.
+#[derive(Debug)]
+pub enum CodecError {
+    Tokenizer(TokenizerError),
+    TooFewSymbols,
+    InvalidHeader(&'static str),
+    InvalidPayload,
+    /// The file is encrypted, see [`encrypt`].
+    Encrypted,
+    /// Decryption failed: wrong password, or the file was changed.
+    Authentication,
+    /// A block does not match its [`BlockCheck`].
+    Checksum,
+    Io(io::Error),
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Tokenizer(err) => err.fmt(f),
+            CodecError::TooFewSymbols => {
+                write!(f, "Cannot build huffman for less than 2 unique symbols")
+            }
+            CodecError::InvalidHeader(reason) => write!(f, "Invalid header: {}", reason),
+            CodecError::InvalidPayload => write!(f, "File is invalid"),
+            CodecError::Encrypted => write!(f, "File is encrypted, a password is needed"),
+            CodecError::Authentication => {
+                write!(f, "Decryption failed: wrong password or tampered file")
+            }
+            CodecError::Checksum => write!(f, "Block checksum mismatch, the file is damaged"),
+            CodecError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Tokenizer(err) => err.source(),
+            CodecError::Io(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+/// For the adapters that implement the `std::io` and tokio traits. Input the
+/// codec cannot handle is `InvalidInput`, corrupt `.huf` data `InvalidData`.
+#[cfg(feature = "std")]
+impl From<CodecError> for std::io::Error {
+    fn from(err: CodecError) -> Self {
+        let kind = match err {
+            CodecError::Io(err) => return err,
+            CodecError::Tokenizer(_) | CodecError::TooFewSymbols => {
+                std::io::ErrorKind::InvalidInput
+            }
+            CodecError::InvalidHeader(_)
+            | CodecError::InvalidPayload
+            | CodecError::Encrypted
+            | CodecError::Authentication
+            | CodecError::Checksum => std::io::ErrorKind::InvalidData,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+impl From<TokenizerError> for CodecError {
+    fn from(err: TokenizerError) -> Self {
+        CodecError::Tokenizer(err)
+    }
+}
+
+impl From<io::Error> for CodecError {
+    fn from(err: io::Error) -> Self {
+        CodecError::Io(err)
+    }
+}
+
+/// Everything the decoder needs besides the payload: how the input was
+/// tokenized and the weight of every token, from which the tree is rebuilt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub tokenizer: Tokenizer,
+    pub table: TokenTable,
+    /// Per-context tables when the payload was coded with the order-1 model.
+    pub contexts: Option<ContextModel>,
+    /// Runs were replaced by run symbols before coding, see [`rle`], and
+    /// expanding them gives this many symbols.
+    pub rle: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompressOptions {
+    pub tokenizer: Tokenizer,
+    /// Code every symbol with a table chosen by the symbol before it.
+    pub order1: bool,
+    /// Collapse runs of a repeated symbol before counting frequencies.
+    pub rle: bool,
+    /// Code the input in independent blocks of about this many bytes and
+    /// append an index of them, so that ranges can be decoded on their own.
+    pub block_size: Option<usize>,
+}
+
+impl Header {
+    /// Order-0 tree rebuilt from the weights, see
+    /// [`HuffTree::from_weights_or_single`].
+    pub fn tree(&self) -> Result<HuffTree, CodecError> {
+        HuffTree::from_weights_or_single(&self.table.weights).ok_or(CodecError::TooFewSymbols)
+    }
+
+    /// Token of `symbol` for listings, or `<run 2^j>` for run symbol `j`.
+    pub fn symbol_label(&self, symbol: u32) -> String {
+        match self.table.tokens.get(symbol as usize) {
+            Some(token) => format!("{:?}", String::from_utf8_lossy(token)),
+            None => format!("<run 2^{}>", symbol as usize - self.table.len()),
+        }
+    }
+
+    /// Token of `symbol` as written in the header, `null` for run symbols.
+    pub fn symbol_json(&self, symbol: u32) -> Value {
+        self.table
+            .tokens
+            .get(symbol as usize)
+            .map_or(Value::Null, |token| token_to_json(token))
+    }
+
+    fn to_json(&self) -> Value {
+        let tokens = self
+            .table
+            .tokens
+            .iter()
+            .map(|token| token_to_json(token))
+            .collect::<Vec<_>>();
+        let mut map = Map::new();
+        map.insert("tokenizer".into(), self.tokenizer.to_string().into());
+        map.insert("tokens".into(), tokens.into());
+        map.insert("weights".into(), self.table.weights.clone().into());
+        if let Some(expanded) = self.rle {
+            map.insert("rle".into(), true.into());
+            map.insert("expanded".into(), expanded.into());
+        }
+        if let Some(contexts) = &self.contexts {
+            map.insert("model".into(), "order1".into());
+            map.insert("symbols".into(), contexts.symbol_count.into());
+            map.insert("contexts".into(), contexts.to_json());
+        }
+        Value::Object(map)
+    }
+
+    fn from_json(mappings: &Value) -> Result<Self, CodecError> {
+        let object = mappings
+            .as_object()
+            .ok_or(CodecError::InvalidHeader("mappings are not an object"))?;
+        if is_stored(mappings) {
+            return Err(CodecError::InvalidHeader("stored member has no code table"));
+        }
+        if is_encrypted_mappings(mappings) {
+            return Err(CodecError::Encrypted);
+        }
+        match object.get("tokenizer") {
+            Some(tokenizer) => {
+                let tokenizer = tokenizer
+                    .as_str()
+                    .and_then(|name| name.parse().ok())
+                    .ok_or(CodecError::InvalidHeader("unknown tokenizer"))?;
+                let tokens = object
+                    .get("tokens")
+                    .and_then(Value::as_array)
+                    .ok_or(CodecError::InvalidHeader("missing tokens"))?
+                    .iter()
+                    .map(token_from_json)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(CodecError::InvalidHeader("malformed token"))?;
+                let weights = object
+                    .get("weights")
+                    .and_then(Value::as_array)
+                    .ok_or(CodecError::InvalidHeader("missing weights"))?
+                    .iter()
+                    .map(|v| v.as_u64().and_then(|w| u32::try_from(w).ok()))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(CodecError::InvalidHeader("malformed weight"))?;
+                check_weight_sum(&weights)?;
+                let rle = match object.get("rle").and_then(Value::as_bool) {
+                    Some(true) => {
+                        let expanded = object
+                            .get("expanded")
+                            .and_then(Value::as_u64)
+                            .ok_or(CodecError::InvalidHeader("missing expanded symbol count"))?;
+                        // Members hold at most one block of tokens.
+                        if expanded > block::MAX_BLOCK_SIZE as u64 {
+                            return Err(CodecError::InvalidHeader(
+                                "expanded symbol count is too large",
+                            ));
+                        }
+                        Some(expanded)
+                    }
+                    _ => None,
+                };
+                let alphabet_size = tokens.len()
+                    + if rle.is_some() {
+                        rle::RUN_SYMBOLS as usize
+                    } else {
+                        0
+                    };
+                if weights.len() != alphabet_size {
+                    return Err(CodecError::InvalidHeader(
+                        "tokens and weights differ in length",
+                    ));
+                }
+                let contexts = match object.get("model").and_then(Value::as_str) {
+                    None | Some("order0") => None,
+                    Some("order1") => {
+                        let symbol_count = object
+                            .get("symbols")
+                            .and_then(Value::as_u64)
+                            .ok_or(CodecError::InvalidHeader("missing symbol count"))?;
+                        let contexts = object.get("contexts").unwrap_or(&Value::Null);
+                        Some(ContextModel::from_json(
+                            symbol_count,
+                            contexts,
+                            alphabet_size,
+                        )?)
+                    }
+                    Some(_) => return Err(CodecError::InvalidHeader("unknown model")),
+                };
+                Ok(Self {
+                    tokenizer,
+                    table: TokenTable { tokens, weights },
+                    contexts,
+                    rle,
+                })
+            }
+            // Files written before tokenizers existed map each char to its
+            // count; serde_json keeps the keys sorted, matching symbol order.
+            None => {
+                let mut table = TokenTable::default();
+                for (k, v) in object {
+                    let weight = v
+                        .as_u64()
+                        .and_then(|w| u32::try_from(w).ok())
+                        .ok_or(CodecError::InvalidHeader("malformed weight"))?;
+                    table.tokens.push(k.as_bytes().to_vec());
+                    table.weights.push(weight);
+                }
+                check_weight_sum(&table.weights)?;
+                Ok(Self {
+                    tokenizer: Tokenizer::Chars,
+                    table,
+                    contexts: None,
+                    rle: None,
+                })
+            }
+        }
+    }
+}
+
+/// The root of the tree weighs the sum of all weights, which has to fit its
+/// `u32`. Encoders never write more, blocks hold at most
+/// [`block::MAX_BLOCK_SIZE`] symbols.
+fn check_weight_sum(weights: &[u32]) -> Result<(), CodecError> {
+    if weights.iter().map(|weight| *weight as u64).sum::<u64>() > u32::MAX as u64 {
+        return Err(CodecError::InvalidHeader(
+            "weights add up to more than 2^32 - 1",
+        ));
+    }
+    Ok(())
+}
+
+fn is_stored(mappings: &Value) -> bool {
+    mappings.get("stored").is_some()
+}
+
+/// Encrypted files are recognised without the `encrypt` feature too, so that
+/// decoding them fails with [`CodecError::Encrypted`].
+fn is_encrypted_mappings(mappings: &Value) -> bool {
+    mappings.get("encrypted").is_some()
+}
+
+/// A token is written as a string when it is UTF-8, as an array of bytes
+/// otherwise.
+fn token_to_json(token: &[u8]) -> Value {
+    match core::str::from_utf8(token) {
+        Ok(s) => Value::from(s),
+        Err(_) => Value::from(token.to_vec()),
+    }
+}
+
+fn token_from_json(token: &Value) -> Option<Vec<u8>> {
+    match token {
+        Value::String(s) => Some(s.as_bytes().to_vec()),
+        Value::Array(bytes) => bytes
+            .iter()
+            .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+            .collect(),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HuffmanDecoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> HuffmanDecoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Parses the bit count line and the mappings that follow it, returning
+    /// the header, the offset of the payload and the number of payload bits.
+    pub fn get_mappings(&self) -> Result<(Header, usize, u64), CodecError> {
+        let (mappings, header_byte_counter, file_size) = self.read_mappings()?;
+        Ok((
+            Header::from_json(&mappings)?,
+            header_byte_counter,
+            file_size,
+        ))
+    }
+
+    fn read_mappings(&self) -> Result<(Value, usize, u64), CodecError> {
+        let counter = self
+            .bytes
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or(CodecError::InvalidHeader("missing bit count"))?;
+        let file_size = bytes_to_u64(&self.bytes[0..counter])?;
+
+        let mut header_byte_counter = counter + 1;
+        let mut counter_n = 0;
+        for b in &self.bytes[counter + 1..] {
+            header_byte_counter += 1;
+            if *b == b'\n' {
+                counter_n += 1;
+                if counter_n == 2 {
+                    break;
+                };
+            } else {
+                counter_n = 0;
+            }
+        }
+        if counter_n != 2 {
+            return Err(CodecError::InvalidHeader("unterminated mappings"));
+        }
+
+        let mappings: Value = serde_json::from_slice(&self.bytes[counter + 1..header_byte_counter])
+            .map_err(|_| CodecError::InvalidHeader("mappings are not valid json"))?;
+        debug!("header is {} bytes", header_byte_counter);
+
+        Ok((mappings, header_byte_counter, file_size))
+    }
+
+    /// Decodes every block of the file, see [`block`].
+    pub fn decode(&self) -> Result<Vec<u8>, CodecError> {
+        let mut decoded = Vec::new();
+        let mut rest = self.bytes;
+        while let Some((block, len)) = Self::new(rest).decode_member()? {
+            decoded.extend_from_slice(&block);
+            rest = &rest[len..];
+            if rest.is_empty() {
+                break;
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Decodes `len` bytes starting at `offset` of the uncompressed data. With
+    /// a block index only the blocks overlapping the range are decoded. The
+    /// range is cut short at the end of the data.
+    pub fn read_range(&self, offset: u64, len: u64) -> Result<Vec<u8>, CodecError> {
+        block::read_range(self.bytes, offset, len)
+    }
+
+    /// Decodes the member at the start of the bytes, returning its data and
+    /// its length in bytes, or `None` for the index that ends a blocked file.
+    /// Blocks are checked against their [`BlockCheck`].
+    fn decode_member(&self) -> Result<Option<(Vec<u8>, usize)>, CodecError> {
+        let (mappings, header_byte_counter, file_size) = self.read_mappings()?;
+        if BlockIndex::is_index(&mappings) {
+            return Ok(None);
+        }
+        let check = BlockCheck::from_json(&mappings)?;
+        let (decoded, member_len) =
+            self.decode_payload(&mappings, header_byte_counter, file_size)?;
+        if let Some(check) = check {
+            check.verify(&decoded)?;
+        }
+        Ok(Some((decoded, member_len)))
+    }
+
+    fn decode_payload(
+        &self,
+        mappings: &Value,
+        header_byte_counter: usize,
+        file_size: u64,
+    ) -> Result<(Vec<u8>, usize), CodecError> {
+        if is_encrypted_mappings(mappings) {
+            return Err(CodecError::Encrypted);
+        }
+        if is_stored(mappings) {
+            let len = usize::try_from(file_size / 8)
+                .ok()
+                .filter(|_| file_size % 8 == 0)
+                .ok_or(CodecError::InvalidHeader(
+                    "stored bit count is not whole bytes",
+                ))?;
+            let stored = self.bytes[header_byte_counter..]
+                .get(..len)
+                .ok_or(CodecError::InvalidPayload)?;
+            return Ok((stored.to_vec(), header_byte_counter + len));
+        }
+        let header = Header::from_json(mappings)?;
+        debug!(
+            "decoding {} payload bits, {} tokenizer",
+            file_size, header.tokenizer
+        );
+
+        let payload = &self.bytes[header_byte_counter..];
+        if (payload.len() as u64) < file_size.div_ceil(8) {
+            return Err(CodecError::InvalidPayload);
+        }
+        let member_len = header_byte_counter + file_size.div_ceil(8) as usize;
+        // Empty input has no symbols and no tree.
+        if header.table.weights.iter().all(|weight| *weight == 0) {
+            return Ok((Vec::new(), member_len));
+        }
+        let tree = header.tree()?;
+        debug!("root node {}", tree);
+        let bits = BitReader::new(payload).take(file_size.try_into().unwrap_or(usize::MAX));
+        let symbols = match &header.contexts {
+            Some(contexts) if !contexts.backed_by(file_size) => {
+                return Err(CodecError::InvalidHeader(
+                    "symbol count is too large for the payload",
+                ))
+            }
+            Some(contexts) => contexts.decode(bits, &tree)?,
+            None => Self::decoding(&tree, bits)?,
+        };
+        let symbols = match header.rle {
+            Some(expanded) => rle::decode_runs(&symbols, header.table.len() as u32, expanded)?,
+            None => symbols,
+        };
+        let decoded = header
+            .table
+            .detokenize(&symbols)
+            .ok_or(CodecError::InvalidPayload)?;
+        Ok((decoded, member_len))
+    }
+
+    fn decoding(tree: &HuffTree, bits: impl Iterator<Item = bool>) -> Result<Vec<u32>, CodecError> {
+        let mut bits = bits.peekable();
+        let mut buffer = Vec::new();
+        while bits.peek().is_some() {
+            buffer.push(
+                tree.decode_symbol(&mut bits)
+                    .ok_or(CodecError::InvalidPayload)?,
+            );
+        }
+        Ok(buffer)
+    }
+}
+
+fn bytes_to_u64(bytes: &[u8]) -> Result<u64, CodecError> {
+    let mut result: u64 = 0;
+    for byte in bytes {
+        if byte.is_ascii_digit() {
+            result = result
+                .checked_mul(10)
+                .and_then(|result| result.checked_add(u64::from(byte - b'0')))
+                .ok_or(CodecError::InvalidHeader("bit count is too large"))?;
+        } else {
+            return Err(CodecError::InvalidHeader("bit count is not numeric"));
+        }
+    }
+    Ok(result)
+}
+
+/// Builds the complete `.huf` contents for `input`: a single member (bit
+/// count, mappings, payload), or with [`CompressOptions::block_size`] one
+/// member per block followed by the block index. Every member carries a
+/// [`BlockCheck`], a file without blocks being one block at offset 0, so
+/// damage fails decoding instead of giving wrong output. Members that coding
+/// would make larger than their input are stored as they are instead.
+///
+/// Blocks are at most [`block::MAX_BLOCK_SIZE`], and larger input is always
+/// blocked, with [`block::DEFAULT_BLOCK_SIZE`] when no block size is given.
+pub fn compress(input: &[u8], options: &CompressOptions) -> Result<Vec<u8>, CodecError> {
+    options.tokenizer.check()?;
+    match options.block_size {
+        Some(block_size) => block::compress_blocks(input, options, block_size),
+        None if input.len() > block::MAX_BLOCK_SIZE => {
+            block::compress_blocks(input, options, block::DEFAULT_BLOCK_SIZE)
+        }
+        None => compress_member(input, options, Some(BlockCheck::new(0, input))),
+    }
+}
+
+/// One member for `input`: Huffman coded, or stored when coding would make
+/// it larger, see [`stored_member`].
+pub(crate) fn compress_member(
+    input: &[u8],
+    options: &CompressOptions,
+    check: Option<BlockCheck>,
+) -> Result<Vec<u8>, CodecError> {
+    let coded = code_member(input, options, check)?;
+    let stored = stored_member(input, check);
+    if coded.len() > stored.len() {
+        debug!(
+            "storing {} bytes, coding takes {} bytes",
+            input.len(),
+            coded.len()
+        );
+        return Ok(stored);
+    }
+    Ok(coded)
+}
+
+/// A member holding `input` as it is, for data that Huffman coding would
+/// only expand. The mappings are `{"stored":true}` and the check, so without
+/// blocks it costs the bit count line and at most 47 bytes over the input.
+fn stored_member(input: &[u8], check: Option<BlockCheck>) -> Vec<u8> {
+    let bits_count = input.len() as u64 * 8;
+    let mut mappings = Map::new();
+    mappings.insert("stored".into(), true.into());
+    if let Some(check) = check {
+        check.insert_into(&mut mappings);
+    }
+    let mut stored = format!("{}\n{}\n\n", bits_count, Value::Object(mappings)).into_bytes();
+    stored.extend_from_slice(input);
+    stored
+}
+
+/// Huffman codes `input` as a single member without blocks, even where
+/// [`compress`] would store it, so that its header can be inspected.
+pub fn compress_coded(input: &[u8], options: &CompressOptions) -> Result<Vec<u8>, CodecError> {
+    code_member(input, options, None)
+}
+
+fn code_member(
+    input: &[u8],
+    options: &CompressOptions,
+    check: Option<BlockCheck>,
+) -> Result<Vec<u8>, CodecError> {
+    let tokenizer = options.tokenizer;
+    let tokens = tokenizer.split(input)?;
+    let (mut table, mut symbols) = TokenTable::build(&tokens);
+    debug!("{} tokens, {} distinct", tokens.len(), table.len());
+
+    let expanded = symbols.len() as u64;
+    if options.rle {
+        symbols = rle::encode_runs(&symbols, table.len() as u32);
+        table.recount(&symbols, table.len() + rle::RUN_SYMBOLS as usize);
+    }
+
+    // Empty input gets a header without symbols and an empty payload.
+    let codes = match HuffTree::from_weights_or_single(&table.weights) {
+        Some(tree) => {
+            debug!("root node {}", tree);
+            tree.codes()
+        }
+        None => Vec::new(),
+    };
+
+    for (token, code) in table.tokens.iter().zip(&codes) {
+        trace!("{:?} | {}", String::from_utf8_lossy(token), code);
+    }
+
+    let mut bit_writer = BitWriter::new(Vec::new());
+
+    let mut contexts = options.order1.then(|| {
+        let lengths: Vec<u8> = codes.iter().map(|code| code.len).collect();
+        ContextModel::build(&symbols, &lengths)
+    });
+    if let Some(model) = &contexts {
+        model.encode(&symbols, &codes, &mut bit_writer)?;
+        if !model.backed_by(bit_writer.bits_written()) {
+            debug!("order-1 payload is too short for its symbol count, coding order-0");
+            contexts = None;
+            bit_writer = BitWriter::new(Vec::new());
+        }
+    }
+    if contexts.is_none() {
+        for symbol in symbols {
+            let code = codes[symbol as usize];
+            bit_writer.write_bits(code.value, code.len as u32)?;
+        }
+    }
+    let bits_count = bit_writer.bits_written();
+    let payload = bit_writer.finish()?;
+
+    let header = Header {
+        tokenizer,
+        table,
+        contexts,
+        rle: options.rle.then_some(expanded),
+    };
+    let mappings = serialize_huffman_mappings(&header, check).unwrap();
+    let mapping_bytes = (mappings + "\n\n").into_bytes();
+    debug!(
+        "header is {} bytes, payload {} bits",
+        mapping_bytes.len(),
+        bits_count
+    );
+
+    let mut compressed = (bits_count.to_string() + "\n").into_bytes();
+    compressed.write_all(&mapping_bytes)?;
+    compressed.write_all(&payload)?;
+    Ok(compressed)
+}
+
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, CodecError> {
+    HuffmanDecoder::new(bytes).decode()
+}
+
+/// See [`HuffmanDecoder::read_range`].
+pub fn decompress_range(bytes: &[u8], offset: u64, len: u64) -> Result<Vec<u8>, CodecError> {
+    HuffmanDecoder::new(bytes).read_range(offset, len)
+}
+
+fn serialize_huffman_mappings(
+    header: &Header,
+    check: Option<BlockCheck>,
+) -> serde_json::error::Result<String> {
+    let mut mappings = header.to_json();
+    if let (Some(check), Value::Object(mappings)) = (check, &mut mappings) {
+        check.insert_into(mappings);
+    }
+    serde_json::to_string(&mappings)
+}
+
+pub fn get_frequency_from_string(s: &str) -> BTreeMap<char, u32> {
+    let mut huff_map = BTreeMap::new();
+
+    for character in s.chars() {
+        *huff_map.entry(character).or_insert(0) += 1
+    }
+
+    huff_map
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::env;
+    use std::fs;
+    use std::hash::{Hash, Hasher};
+
+    const PATH_TO_FILE: &str = "huffman.txt";
+
+    #[test]
+    fn check_frequency_of_some_english_characters() {
+        let current_dir = env::current_dir().expect("Failed to get current directory");
+
+        // Combine the current directory with the relative path
+        let file_path = current_dir.join(PATH_TO_FILE);
+        let file_str = fs::read_to_string(file_path).expect("It should be valid path");
+
+        let hash_map = get_frequency_from_string(&file_str);
+
+        assert_eq!(hash_map.get(&'X'), Some(333).as_ref());
+        assert_eq!(hash_map.get(&'t'), Some(223000).as_ref())
+    }
+
+    #[test]
+    fn check_frequency_of_some_non_english_characters() {
+        let current_dir = env::current_dir().expect("Failed to get current directory");
+
+        // Combine the current directory with the relative path
+        let file_path = current_dir.join(PATH_TO_FILE);
+        let file_str = fs::read_to_string(file_path).expect("It should be valid path");
+
+        let hash_map = get_frequency_from_string(&file_str);
+
+        assert_eq!(hash_map.get(&'â'), Some(56).as_ref());
+        assert_eq!(hash_map.get(&'À'), Some(5).as_ref());
+    }
+
+    #[test]
+    fn check_frequency_of_some_other_characters() {
+        let current_dir = env::current_dir().expect("Failed to get current directory");
+
+        // Combine the current directory with the relative path
+        let file_path = current_dir.join(PATH_TO_FILE);
+        let file_str = fs::read_to_string(file_path).expect("It should be valid path");
+
+        let hash_map = get_frequency_from_string(&file_str);
+
+        assert_eq!(hash_map.get(&'\n'), Some(73589).as_ref());
+        assert_eq!(hash_map.get(&'$'), Some(2).as_ref());
+    }
+
+    fn hash_of(bytes: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn encode_should_produce_identical_output_on_every_run() {
+        let current_dir = env::current_dir().expect("Failed to get current directory");
+        let file_bytes = fs::read(current_dir.join(PATH_TO_FILE)).expect("It should be valid path");
+
+        let expected = hash_of(&compress(&file_bytes, &CompressOptions::default()).unwrap());
+        for _ in 0..4 {
+            assert_eq!(
+                hash_of(&compress(&file_bytes, &CompressOptions::default()).unwrap()),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn encode_output_should_not_depend_on_the_platform() {
+        // Pins the bytes this version writes. Writing different bytes is a
+        // format change; it is only fine while the old bytes still decode.
+        let expected = b"23\n{\"tokenizer\":\"chars\",\"tokens\":[\"a\",\"b\",\"c\",\"d\",\"r\"],\"weights\":[5,2,1,1,2]}\n\n\x7c\xa8\xf8";
+        assert_eq!(decompress(expected).unwrap(), b"abracadabra");
+        assert_eq!(
+            compress_coded(b"abracadabra", &CompressOptions::default()).unwrap(),
+            expected.to_vec()
+        );
+        // Coding costs more than storing input this short.
+        assert_eq!(
+            compress(b"abracadabra", &CompressOptions::default()).unwrap(),
+            b"88\n{\"crc32\":2791670068,\"offset\":0,\"stored\":true}\n\nabracadabra".to_vec()
+        );
+    }
+
+    #[test]
+    fn decode_should_read_headers_written_before_tokenizers() {
+        let legacy = b"23\n{\"a\":5,\"b\":2,\"c\":1,\"d\":1,\"r\":2}\n\n\x7c\xa8\xf8";
+        assert_eq!(decompress(legacy).unwrap(), b"abracadabra");
+    }
+
+    /// Bytes of a linear congruential generator, which Huffman coding
+    /// cannot shrink.
+    pub(crate) fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x853c_49e6_748f_ea9b_u64;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn incompressible_input_should_be_stored() {
+        let input = noise(10_000);
+        for tokenizer in [Tokenizer::Bytes, Tokenizer::NGrams(2)] {
+            let options = CompressOptions {
+                tokenizer,
+                order1: true,
+                ..Default::default()
+            };
+            let compressed = compress(&input, &options).unwrap();
+            let (mappings, header_len, _) =
+                HuffmanDecoder::new(&compressed).read_mappings().unwrap();
+            assert!(is_stored(&mappings));
+            assert!(header_len <= "80000\n".len() + 47);
+            assert_eq!(compressed.len(), input.len() + header_len);
+            assert_eq!(decompress(&compressed).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn stored_members_should_have_no_code_table() {
+        let compressed = compress(
+            &noise(1000),
+            &CompressOptions {
+                tokenizer: Tokenizer::Bytes,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            HuffmanDecoder::new(&compressed).get_mappings(),
+            Err(CodecError::InvalidHeader("stored member has no code table"))
+        ));
+        assert!(
+            compress_coded(
+                &noise(1000),
+                &CompressOptions {
+                    tokenizer: Tokenizer::Bytes,
+                    ..Default::default()
+                }
+            )
+            .unwrap()
+            .len()
+                > compressed.len()
+        );
+    }
+
+    #[test]
+    fn decode_should_reject_overlong_bit_counts() {
+        let huge = b"99999999999999999999999\n{\"a\":1,\"b\":1}\n\n\x00";
+        assert!(matches!(
+            decompress(huge),
+            Err(CodecError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn every_tokenizer_should_round_trip() {
+        let current_dir = env::current_dir().expect("Failed to get current directory");
+        let file_bytes = fs::read(current_dir.join(PATH_TO_FILE)).expect("It should be valid path");
+        let sample = &file_bytes[..20_000];
+
+        for tokenizer in [
+            Tokenizer::Bytes,
+            Tokenizer::Chars,
+            Tokenizer::Words { min_count: 2 },
+            Tokenizer::NGrams(3),
+        ] {
+            let compressed = compress(
+                sample,
+                &CompressOptions {
+                    tokenizer,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(decompress(&compressed).unwrap(), sample, "{}", tokenizer);
+        }
+    }
+
+    #[test]
+    fn bytes_tokenizer_should_round_trip_binary_input() {
+        let input: Vec<u8> = (0..=255u8).chain([0, 0, 0, 255]).collect();
+        let compressed = compress(
+            &input,
+            &CompressOptions {
+                tokenizer: Tokenizer::Bytes,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), input);
+    }
+
+    #[test]
+    fn words_should_beat_chars_on_english_text() {
+        let current_dir = env::current_dir().expect("Failed to get current directory");
+        let file_bytes = fs::read(current_dir.join(PATH_TO_FILE)).expect("It should be valid path");
+
+        let chars = compress(&file_bytes, &CompressOptions::default()).unwrap();
+        let words = compress(
+            &file_bytes,
+            &CompressOptions {
+                tokenizer: Tokenizer::Words { min_count: 2 },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(words.len() < chars.len());
+    }
+
+    #[test]
+    fn order1_should_round_trip_with_every_tokenizer() {
+        let current_dir = env::current_dir().expect("Failed to get current directory");
+        let file_bytes = fs::read(current_dir.join(PATH_TO_FILE)).expect("It should be valid path");
+        let sample = &file_bytes[..20_000];
+
+        for tokenizer in [
+            Tokenizer::Bytes,
+            Tokenizer::Chars,
+            Tokenizer::Words { min_count: 2 },
+            Tokenizer::NGrams(2),
+        ] {
+            let options = CompressOptions {
+                tokenizer,
+                order1: true,
+                ..Default::default()
+            };
+            let compressed = compress(sample, &options).unwrap();
+            assert_eq!(decompress(&compressed).unwrap(), sample, "{}", tokenizer);
+        }
+    }
+
+    #[test]
+    fn order1_should_beat_order0_on_english_text() {
+        let current_dir = env::current_dir().expect("Failed to get current directory");
+        let file_bytes = fs::read(current_dir.join(PATH_TO_FILE)).expect("It should be valid path");
+
+        let order0 = compress(&file_bytes, &CompressOptions::default()).unwrap();
+        let order1 = compress(
+            &file_bytes,
+            &CompressOptions {
+                order1: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // 23% smaller when measured, see the README.
+        assert!(
+            order1.len() * 5 <= order0.len() * 4,
+            "order-0: {} bytes, order-1: {} bytes",
+            order0.len(),
+            order1.len()
+        );
+    }
+
+    #[test]
+    fn order1_should_fall_back_when_symbols_cost_no_bits() {
+        // After a always comes b and the other way around, so every symbol
+        // but the first costs zero bits under order-1.
+        let input = b"ab".repeat(100_000);
+        let options = CompressOptions {
+            order1: true,
+            ..Default::default()
+        };
+        let compressed = compress_coded(&input, &options).unwrap();
+
+        let (header, _, bits) = HuffmanDecoder::new(&compressed).get_mappings().unwrap();
+        assert!(header.contexts.is_none());
+        assert!(bits >= input.len() as u64);
+        assert_eq!(decompress(&compressed).unwrap(), input);
+    }
+
+    #[test]
+    fn forged_symbol_count_should_be_rejected() {
+        let options = CompressOptions {
+            order1: true,
+            ..Default::default()
+        };
+        let compressed = compress_coded(&b"ab".repeat(20), &options).unwrap();
+        let (mut mappings, header_len, bits) =
+            HuffmanDecoder::new(&compressed).read_mappings().unwrap();
+        assert!(mappings["symbols"].as_u64().is_some());
+        mappings["symbols"] = serde_json::json!(u64::MAX);
+        let mut forged = format!("{}\n{}\n\n", bits, mappings).into_bytes();
+        forged.extend_from_slice(&compressed[header_len..]);
+
+        assert!(matches!(
+            decompress(&forged),
+            Err(CodecError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn fuzz_regressions_should_fail_without_panicking() {
+        for entry in fs::read_dir("fuzz/regressions/decompress").unwrap() {
+            let path = entry.unwrap().path();
+            let bytes = fs::read(&path).unwrap();
+            assert!(decompress(&bytes).is_err(), "{}", path.display());
+            assert!(
+                decompress_range(&bytes, 3, 100).is_err(),
+                "{}",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn weights_past_the_root_weight_should_be_rejected() {
+        let header = b"2\n{\"a\":4294967295,\"b\":4294967295}\n\n\x00";
+        assert!(matches!(
+            decompress(header),
+            Err(CodecError::InvalidHeader(_))
+        ));
+        assert!(HuffTree::from_weights(&[u32::MAX, 1]).is_none());
+    }
+
+    #[test]
+    fn weights_past_u32_should_be_rejected() {
+        let old = b"2\n{\"a\":4294967296,\"b\":1}\n\n\x00";
+        let current = b"2\n{\"tokenizer\":\"chars\",\"tokens\":[\"a\",\"b\"],\"weights\":[4294967296,1]}\n\n\x00";
+        for header in [&old[..], &current[..]] {
+            assert!(matches!(
+                decompress(header),
+                Err(CodecError::InvalidHeader("malformed weight"))
+            ));
+        }
+    }
+
+    #[test]
+    fn forged_expanded_count_should_be_rejected() {
+        let options = CompressOptions {
+            rle: true,
+            ..Default::default()
+        };
+        let compressed = compress_coded(&b"a".repeat(1000), &options).unwrap();
+        let (mut mappings, header_len, bits) =
+            HuffmanDecoder::new(&compressed).read_mappings().unwrap();
+        assert_eq!(mappings["expanded"], 1000);
+        mappings["expanded"] = serde_json::json!(block::MAX_BLOCK_SIZE as u64 + 1);
+        let mut forged = format!("{}\n{}\n\n", bits, mappings).into_bytes();
+        forged.extend_from_slice(&compressed[header_len..]);
+
+        assert!(matches!(
+            decompress(&forged),
+            Err(CodecError::InvalidHeader(
+                "expanded symbol count is too large"
+            ))
+        ));
+    }
+
+    #[test]
+    fn rle_should_round_trip_and_shrink_long_runs() {
+        let mut input = b"header".to_vec();
+        input.extend(std::iter::repeat(0u8).take(100_000));
+        input.extend_from_slice(b"  trailer\n");
+        input.extend(std::iter::repeat(b' ').take(5_000));
+
+        for order1 in [false, true] {
+            let plain = compress(
+                &input,
+                &CompressOptions {
+                    tokenizer: Tokenizer::Bytes,
+                    order1,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let rle = compress(
+                &input,
+                &CompressOptions {
+                    tokenizer: Tokenizer::Bytes,
+                    order1,
+                    rle: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(decompress(&rle).unwrap(), input);
+            assert!(rle.len() * 10 < plain.len());
+        }
+    }
+
+    mod round_trip {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn tokenizer() -> impl Strategy<Value = Tokenizer> {
+            prop_oneof![
+                Just(Tokenizer::Bytes),
+                Just(Tokenizer::Chars),
+                (1u32..4).prop_map(|min_count| Tokenizer::Words { min_count }),
+                (1usize..5).prop_map(Tokenizer::NGrams),
+            ]
+        }
+
+        fn options(
+            tokenizer: impl Strategy<Value = Tokenizer>,
+        ) -> impl Strategy<Value = CompressOptions> {
+            (
+                tokenizer,
+                any::<bool>(),
+                any::<bool>(),
+                prop::option::of(1usize..200),
+            )
+                .prop_map(|(tokenizer, order1, rle, block_size)| CompressOptions {
+                    tokenizer,
+                    order1,
+                    rle,
+                    block_size,
+                })
+        }
+
+        /// Text in which symbol `i` occurs `weights[i]` times, shuffled.
+        fn text_with_weights(weights: Vec<u32>) -> impl Strategy<Value = String> {
+            let symbols: Vec<char> = weights
+                .iter()
+                .enumerate()
+                .flat_map(|(i, weight)| {
+                    let symbol = char::from_u32(0x100 + i as u32).unwrap();
+                    std::iter::repeat(symbol).take(*weight as usize)
+                })
+                .collect();
+            Just(symbols)
+                .prop_shuffle()
+                .prop_map(|symbols| symbols.into_iter().collect())
+        }
+
+        fn fibonacci(n: usize) -> Vec<u32> {
+            let mut weights = vec![1, 1];
+            while weights.len() < n {
+                weights.push(weights[weights.len() - 1] + weights[weights.len() - 2]);
+            }
+            weights.truncate(n);
+            weights
+        }
+
+        fn assert_round_trip(input: &[u8], options: &CompressOptions) -> Result<(), TestCaseError> {
+            let compressed = compress(input, options).unwrap();
+            prop_assert_eq!(decompress(&compressed).unwrap(), input);
+            Ok(())
+        }
+
+        proptest! {
+            #[test]
+            fn strings_should_round_trip(input in ".*", options in options(tokenizer())) {
+                assert_round_trip(input.as_bytes(), &options)?;
+            }
+
+            #[test]
+            fn bytes_should_round_trip(
+                input in prop::collection::vec(any::<u8>(), 0..2000),
+                options in options(tokenizer().prop_filter("needs UTF-8", |t| *t != Tokenizer::Chars)),
+            ) {
+                assert_round_trip(&input, &options)?;
+            }
+
+            #[test]
+            fn single_symbol_should_round_trip(
+                symbol in any::<char>(),
+                count in 1usize..2000,
+                options in options(tokenizer()),
+            ) {
+                assert_round_trip(symbol.to_string().repeat(count).as_bytes(), &options)?;
+            }
+
+            #[test]
+            fn skewed_weights_should_round_trip(
+                input in prop::collection::vec(1u32..100, 1..50).prop_flat_map(text_with_weights),
+                options in options(Just(Tokenizer::Chars)),
+            ) {
+                assert_round_trip(input.as_bytes(), &options)?;
+            }
+        }
+
+        proptest! {
+            // Inputs here run to tens of thousands of symbols.
+            #![proptest_config(ProptestConfig::with_cases(32))]
+
+            #[test]
+            fn huge_alphabets_should_round_trip(
+                input in prop::collection::vec(1u32..4, 1000..5000).prop_flat_map(text_with_weights),
+                options in options(Just(Tokenizer::Chars)),
+            ) {
+                assert_round_trip(input.as_bytes(), &options)?;
+            }
+
+            #[test]
+            fn fibonacci_weights_should_round_trip(
+                input in (2usize..22).prop_flat_map(|n| text_with_weights(fibonacci(n))),
+                options in options(Just(Tokenizer::Chars)),
+            ) {
+                assert_round_trip(input.as_bytes(), &options)?;
+            }
+        }
+
+        #[test]
+        fn fibonacci_weights_should_give_the_deepest_tree() {
+            let tree = HuffTree::from_weights(&fibonacci(21)).unwrap();
+            assert_eq!(tree.code_lengths().iter().max(), Some(&20));
+        }
+    }
+}
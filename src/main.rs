@@ -1,628 +1,496 @@
-use std::cell::RefCell;
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
-use std::fmt::{Display, Formatter};
+use std::borrow::Cow;
+use std::env;
+use std::ffi::OsString;
 use std::fs;
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Read, Write};
-use std::ops::Deref;
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
+use std::process::ExitCode;
 
-use clap::Parser;
-use log::error;
-use serde_json::Value;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use log::{debug, error, info, warn, LevelFilter};
 use thiserror::Error;
 
+use compressor::block::DEFAULT_BLOCK_SIZE;
+use compressor::encrypt::{self, decrypt, is_encrypted};
+use compressor::stats::analyze;
+use compressor::{
+    compress, compress_coded, compress_level, decompress, decompress_range, CodecError,
+    CompressOptions, Header, HuffmanDecoder, Input, Level, Tokenizer,
+};
+
 #[derive(Parser, Default, Debug)]
 #[command(
     version,
     about,
     long_about = "huffman compression implementation in rust"
 )]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 #[clap(name = "compressor")]
 struct Args {
     #[arg(help = "path of file to compress", required = true)]
-    path: String,
+    path: Option<String>,
     #[arg(short, help = "option to decode huffman encoded string")]
     decode: bool,
+    #[command(flatten)]
+    coding: CodingArgs,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        num_args = 0..=1,
+        default_missing_value = "1048576",
+        help = "code the input in independent blocks (1 MiB without a value) and add an index for --range"
+    )]
+    block_size: Option<usize>,
+    #[arg(
+        short,
+        long,
+        value_name = "1-9",
+        value_parser = clap::value_parser!(u8).range(1..=9),
+        conflicts_with_all = ["tokenizer", "order1", "rle"],
+        help = "try the first N of: bytes, chars, words, bytes --rle, ngram:2, chars --order1, words --order1, \
+                bytes --order1 --rle, ngram:2 --order1, and keep the smallest output; \
+                1 is fastest, 9 smallest. -1 to -9 are short for -l 1 to -l 9"
+    )]
+    level: Option<u8>,
+    #[arg(
+        long,
+        value_name = "OFFSET:LEN",
+        requires = "decode",
+        value_parser = parse_range,
+        help = "with -d, write LEN bytes from OFFSET of the decoded data to stdout"
+    )]
+    range: Option<(u64, u64)>,
+    #[arg(
+        long,
+        requires = "decode",
+        conflicts_with = "range",
+        help = "with -d, skip blocks that fail their checksum, log their byte ranges and write the rest"
+    )]
+    recover: bool,
+    #[arg(
+        long,
+        help = "memory-map the input file instead of reading it into memory; \
+                compressing then uses 1 MiB blocks unless --block-size is given"
+    )]
+    mmap: bool,
+    #[arg(
+        long,
+        conflicts_with = "decode",
+        help = "encrypt the output (Argon2id, ChaCha20-Poly1305) with the password in HUF_PASSWORD, \
+                or else the first line of stdin; -d asks for it the same way"
+    )]
+    encrypt: bool,
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = ArgAction::Count,
+        help = "log more, repeat for more detail (-vv debug, -vvv trace)"
+    )]
+    verbose: u8,
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        help = "only log errors"
+    )]
+    quiet: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Error, Debug)]
-enum FindError {
-    #[error("Error reading File: {0}")]
-    ReadFileError(#[from] std::io::Error),
-}
-
-#[derive(Debug, Clone)]
-struct HuffNode {
-    weight: u32,
-    element: Option<char>,
-    left: Option<TreeNodeRef>,
-    right: Option<TreeNodeRef>,
-    id: u32,
+/// Rewrites the gzip style `-1` to `-9` into `--level=1` to `--level=9`,
+/// clap has no flags taking their value from the flag name.
+fn expand_levels(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut expanded = Vec::new();
+    let mut options_ended = false;
+    for arg in args {
+        match arg.to_str().map(str::as_bytes) {
+            Some(b"--") => options_ended = true,
+            Some(&[b'-', level @ b'1'..=b'9']) if !options_ended => {
+                expanded.push(format!("--level={}", level as char).into());
+                continue;
+            }
+            _ => {}
+        }
+        expanded.push(arg);
+    }
+    expanded
 }
 
-type TreeNodeRef = Rc<HuffNode>;
-
-impl Display for HuffNode {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "HuffNode {{ weight: {}, element: {:?} id: {:?} }}",
-            self.weight, self.element, self.id
-        )
-    }
+fn parse_range(s: &str) -> Result<(u64, u64), String> {
+    let (offset, len) = s.split_once(':').ok_or("expected OFFSET:LEN")?;
+    let parse = |n: &str| n.parse::<u64>().map_err(|e| format!("{n:?}: {e}"));
+    Ok((parse(offset)?, parse(len)?))
 }
 
-impl HuffNode {
-    fn new(left: HuffNode, right: HuffNode, id: u32) -> HuffNode {
-        Self {
-            weight: left.weight() + right.weight(),
-            element: None,
-            left: Option::from(Rc::new(left)),
-            right: Option::from(Rc::new(right)),
-            id,
+impl Args {
+    /// Warnings and errors by default; `RUST_LOG` still overrides this.
+    fn log_level(&self) -> LevelFilter {
+        if self.quiet {
+            return LevelFilter::Error;
         }
-    }
-
-    fn is_leaf(&self) -> bool {
-        match self.element {
-            None => false,
-            Some(_) => true,
+        match self.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
         }
     }
+}
 
-    fn weight(&self) -> u32 {
-        self.weight
-    }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Report sizes, entropy and code lengths for a file without writing anything
+    #[command(visible_alias = "analyze")]
+    Stats {
+        #[arg(help = "path of file to analyze")]
+        path: String,
+        #[arg(long, help = "print the report as JSON")]
+        json: bool,
+        #[command(flatten)]
+        coding: CodingArgs,
+    },
+    /// Print the Huffman tree of a file, or of the header of a .huf file
+    Tree {
+        #[arg(help = "path of a file to build the tree for, or of a .huf file")]
+        path: String,
+        #[arg(short, long, value_enum, default_value_t = TreeFormat::Codes, help = "output format")]
+        format: TreeFormat,
+        #[command(flatten)]
+        coding: CodingArgs,
+    },
 }
 
-impl PartialEq for HuffNode {
-    fn eq(&self, other: &Self) -> bool {
-        if other.weight().eq(&self.weight()) {
-            self.element.eq(&other.element)
-        } else {
-            other.weight().eq(&self.weight())
-        }
-    }
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TreeFormat {
+    /// symbol, token, weight and code of every symbol
+    Codes,
+    /// Graphviz DOT, render with `dot -Tsvg`
+    Dot,
+    /// nested JSON nodes
+    Json,
 }
 
-impl PartialOrd for HuffNode {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if other.weight().eq(&self.weight()) {
-            match (self.element, other.element) {
-                (None, None) => Some(other.id.cmp(&self.id)),
-                (Some(_), None) => Option::from(Ordering::Greater),
-                (None, Some(_)) => Option::from(Ordering::Less),
-                (Some(el1), Some(el2)) => Option::from(el1.cmp(&el2)),
-            }
-        } else {
-            Some(other.weight().cmp(&self.weight()))
-        }
-    }
+#[derive(clap::Args, Default, Debug)]
+struct CodingArgs {
+    #[arg(
+        short,
+        long,
+        default_value_t = Tokenizer::Chars,
+        help = "symbols to code: bytes, chars, words[:MIN_COUNT] or ngram:N"
+    )]
+    tokenizer: Tokenizer,
+    #[arg(
+        long,
+        help = "pick each symbol's code by the symbol before it (order-1 model)"
+    )]
+    order1: bool,
+    #[arg(long, help = "collapse runs of a repeated symbol before coding")]
+    rle: bool,
 }
 
-impl Ord for HuffNode {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if other.weight().eq(&self.weight()) {
-            match (self.element, other.element) {
-                (None, None) => other.id.cmp(&self.id),
-                (Some(_), None) => Ordering::Greater,
-                (None, Some(_)) => Ordering::Less,
-                (Some(el1), Some(el2)) => el1.cmp(&el2),
-            }
-        } else {
-            other.weight().cmp(&self.weight())
+impl From<&CodingArgs> for CompressOptions {
+    fn from(args: &CodingArgs) -> Self {
+        Self {
+            tokenizer: args.tokenizer,
+            order1: args.order1,
+            rle: args.rle,
+            block_size: None,
         }
     }
 }
 
-impl Eq for HuffNode {}
-
-struct BitsEncoder {
-    bytes: Vec<u8>,
-    current_byte: u8,
-    byte_count: usize,
-    bits_count: u64,
+#[derive(Error, Debug)]
+enum FindError {
+    #[error("Error reading File: {0}")]
+    ReadFileError(#[from] std::io::Error),
+    #[error(transparent)]
+    CodecError(#[from] CodecError),
+    #[error("Error reading password: {0}")]
+    PasswordError(std::io::Error),
+    #[error("The password is empty")]
+    EmptyPassword,
+    #[error("Parent folder not found")]
+    NoParent,
 }
 
-const BITS_PER_BYTE: usize = 8;
-
-impl BitsEncoder {
-    fn new() -> Self {
-        Self {
-            bytes: vec![],
-            current_byte: 0,
-            byte_count: 0,
-            bits_count: 0,
-        }
-    }
-
-    fn add_bit(&mut self, bit: bool) {
-        if self.byte_count == BITS_PER_BYTE {
-            self.flush_current_byte();
-        }
-        if bit {
-            self.current_byte |= 1 << (BITS_PER_BYTE - self.byte_count - 1);
+/// `HUF_PASSWORD`, or else the first line of stdin without its line ending.
+fn read_password() -> Result<String, FindError> {
+    let password = match env::var("HUF_PASSWORD") {
+        Ok(password) => password,
+        Err(_) => {
+            if io::stdin().is_terminal() {
+                eprint!("password: ");
+            }
+            let mut line = String::new();
+            io::stdin()
+                .read_line(&mut line)
+                .map_err(FindError::PasswordError)?;
+            line.trim_end_matches(['\r', '\n']).to_owned()
         }
-        self.byte_count += 1;
-        self.bits_count += 1;
-    }
-
-    fn flush_current_byte(&mut self) {
-        self.bytes.push(self.current_byte);
-        self.current_byte = 0;
-        self.byte_count = 0;
-    }
-
-    fn encode(&self) -> &[u8] {
-        &self.bytes
+    };
+    if password.is_empty() {
+        return Err(FindError::EmptyPassword);
     }
+    Ok(password)
 }
 
-struct HuffmanDecoder {
-    bytes: Vec<u8>,
-    path: PathBuf,
+/// `bytes`, decrypted with the password from [`read_password`] if they are an
+/// encrypted file.
+fn decrypted(bytes: &[u8]) -> Result<Cow<'_, [u8]>, FindError> {
+    if !is_encrypted(bytes) {
+        return Ok(Cow::Borrowed(bytes));
+    }
+    let password = read_password()?;
+    Ok(Cow::Owned(decrypt(bytes, password.as_bytes())?))
 }
 
-impl HuffmanDecoder {
-    fn new(bytes: Vec<u8>, path: PathBuf) -> Self {
-        Self { bytes, path }
-    }
+fn main() -> ExitCode {
+    let args = Args::parse_from(expand_levels(std::env::args_os()));
+    env_logger::Builder::new()
+        .filter_level(args.log_level())
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
 
-    fn get_mappings(&self) -> (HashMap<char, u32>, usize, u64) {
-        // Read from the file line by line
-        let mut counter = 0;
-        let mut header_byte_counter = 0;
-        for byte in self.bytes.bytes() {
-            let b = byte.unwrap();
-            // print!("{:?}", b);
-            header_byte_counter += 1;
-            if b == b'\n' {
-                break;
-            }
-            counter += 1;
-        }
-        println!("number bytes {:?}", &self.bytes[0..counter]);
-        let file_size = bytes_to_u64(&self.bytes[0..counter]);
-
-        let mut buf = vec![];
-        let mut counter_n = 0;
-        println!("len ========={}", self.bytes.len());
-        for byte in self.bytes[counter + 1..].bytes() {
-            let b = byte.unwrap();
-            header_byte_counter += 1;
-            if b == b'\n' {
-                counter_n += 1;
-                buf.push(b);
-                if counter_n == 2 {
-                    break;
-                };
-            } else {
-                counter_n = 0;
-                buf.push(b);
-            }
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error!("{}", err);
+            ExitCode::FAILURE
         }
-        println!("{:?}", &buf);
-        // let huff_mappings = String::from_utf8_lossy(&buf);
-        let mappings: Value = serde_json::from_slice(&buf).unwrap();
-        println!("{:?}", &mappings);
-        println!("{:?}", &header_byte_counter);
-
-        let huff_map: HashMap<char, u32> = mappings
-            .as_object()
-            .unwrap()
-            .iter()
-            .map(|(k, v)| (k.chars().next().unwrap(), v.as_u64().unwrap() as u32))
-            .collect();
-        (huff_map, header_byte_counter, file_size)
     }
+}
 
-    fn decode(&self) {
-        let (mappings, header_byte_counter, file_size) = self.get_mappings();
-        println!("{:?}", &mappings);
-        let mut priority_queue = get_priority_queue(&mappings);
-
-        match get_huffman_tree_node(&mut priority_queue) {
-            None => {
-                panic!("Something went wrong")
-            }
-            Some(node) => {
-                println!("root node {}", node);
-                let huff_map = traverse_and_get_prefixes(Rc::new(node.clone()));
-
-                for (key, value) in &huff_map {
-                    let bit_str: String =
-                        value.iter().map(|x| if *x { '1' } else { '0' }).collect();
-                    println!("{} | {}", key, bit_str);
-                }
-                self.decoding(&Rc::new(node), header_byte_counter, file_size);
-            }
-        }
+fn run(args: Args) -> Result<(), FindError> {
+    match &args.command {
+        Some(Command::Stats { path, json, coding }) => return stats(path, &coding.into(), *json),
+        Some(Command::Tree {
+            path,
+            format,
+            coding,
+        }) => return tree(path, &coding.into(), *format),
+        None => {}
     }
 
-    fn decoding(&self, huff_node: &TreeNodeRef, start_from: usize, file_size: u64) {
-        println!("{},{}", start_from, file_size);
-        let mut tmp_node = Rc::clone(&huff_node);
-        let mut buffer = Vec::new();
-        let mut counter: u64 = 0;
-        for byte in &self.bytes[start_from..] {
-            // print!("{:?}, ", byte);
-            for i in (0..BITS_PER_BYTE).rev() {
-                if counter >= file_size {
-                    break;
-                }
-                counter += 1;
-                let bit = (*byte >> i) & 1;
-                if bit == 0 {
-                    // print!("0");
-                    let tmp = Rc::clone(&tmp_node);
-                    match &tmp.left {
-                        None => {
-                            panic!("File is invalid");
-                        }
-                        Some(node_ref) => {
-                            let node = Rc::clone(node_ref);
-                            let next_node = match node.element {
-                                None => Rc::clone(&node),
-                                Some(c) => {
-                                    buffer.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
-                                    Rc::clone(huff_node)
-                                }
-                            };
-                            tmp_node = next_node;
-                        }
-                    };
-                } else {
-                    // print!("1");
-                    let tmp = Rc::clone(&tmp_node);
-                    match &tmp.right {
-                        None => {
-                            panic!("File is invalid");
-                        }
-                        Some(node_ref) => {
-                            let node = Rc::clone(node_ref);
-                            let next_node = match node.element {
-                                None => Rc::clone(&node),
-                                Some(c) => {
-                                    buffer.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
-                                    Rc::clone(huff_node)
-                                }
-                            };
-                            tmp_node = next_node;
-                        }
-                    };
-                }
-            }
-        }
-
-        match fs::write(&self.path, buffer) {
-            Ok(_) => {
-                println!("File written successfully")
-            }
-            Err(e) => {
-                eprintln!("error: {}", e);
-            }
+    let path = args.path.unwrap();
+    if let Some((offset, len)) = args.range {
+        decode_range(&path, offset, len, args.mmap)
+    } else if args.decode {
+        decode(&path, args.recover, args.mmap)
+    } else {
+        // Mapped input is meant for files too large to buffer, so it is never
+        // tokenized whole.
+        let block_size = match args.block_size {
+            None if args.mmap => Some(DEFAULT_BLOCK_SIZE),
+            block_size => block_size,
         };
+        let options = CompressOptions {
+            block_size,
+            ..(&args.coding).into()
+        };
+        let level = args.level.map(|level| Level::new(level).unwrap());
+        encode(&path, &options, level, args.encrypt, args.mmap)
     }
 }
 
-fn bytes_to_u64(bytes: &[u8]) -> u64 {
-    let mut result: u64 = 0;
-    for byte in bytes {
-        if byte.is_ascii_digit() {
-            result = result * 10 + (*byte as char).to_digit(10).unwrap() as u64;
-        } else {
-            panic!("cannot convert non numeric to number")
-        }
+/// Runs `write` on the locked stdout. A reader that goes away early, as
+/// `head` does, ends the output without an error.
+fn to_stdout(write: impl FnOnce(&mut io::StdoutLock) -> io::Result<()>) -> Result<(), FindError> {
+    let mut stdout = io::stdout().lock();
+    match write(&mut stdout).and_then(|()| stdout.flush()) {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
     }
-    result
 }
 
-fn main() {
-    let args = Args::parse();
-
-    let path = args.path;
-    let dec = args.decode;
+fn stats(path: &str, options: &CompressOptions, json: bool) -> Result<(), FindError> {
+    let stats = analyze(&fs::read(path)?, options)?;
+    to_stdout(|out| match json {
+        true => writeln!(out, "{}", stats.to_json()),
+        false => write!(out, "{}", stats),
+    })
+}
 
-    if dec {
-        decode(&path);
+/// The header of `path` if it is a .huf file, otherwise the header `path`
+/// would be coded with.
+fn read_header(path: &str, options: &CompressOptions) -> Result<Header, FindError> {
+    let bytes = fs::read(path)?;
+    let compressed = if Path::new(path).extension().is_some_and(|ext| ext == "huf") {
+        bytes
     } else {
-        encode(&path);
-    }
+        compress_coded(&bytes, options)?
+    };
+    Ok(HuffmanDecoder::new(&compressed).get_mappings()?.0)
 }
 
-fn encode(path: &String) {
-    let str_result = fs::read_to_string(&path).map_err(|err| FindError::ReadFileError(err));
-
-    match str_result {
-        Ok(file_str) => {
-            let huff_freq = get_frequency_from_string(&file_str);
-            println!("{:?}", &huff_freq);
-
-            if huff_freq.len() < 2 {
-                panic!("Cannot build huffman for less than 2 unique character");
-            }
-
-            let mut priority_queue = get_priority_queue(&huff_freq);
-
-            if let Some(node) = get_huffman_tree_node(&mut priority_queue) {
-                println!("root node {}", node);
-                let huff_map = traverse_and_get_prefixes(Rc::new(node));
-
-                for (key, value) in &huff_map {
-                    let bit_str: String =
-                        value.iter().map(|x| if *x { '1' } else { '0' }).collect();
-                    println!("{} | {}", key, bit_str);
-                }
-
-                let mut bits_encoder = BitsEncoder::new();
-
-                for c in file_str.chars() {
-                    for bit in huff_map.get(&c).unwrap() {
-                        // print!("{}", if *bit { "1"} else {"0"});
-                        bits_encoder.add_bit(*bit)
+fn tree(path: &str, options: &CompressOptions, format: TreeFormat) -> Result<(), FindError> {
+    let header = read_header(path, options)?;
+    let tree = header.tree()?;
+    to_stdout(|out| {
+        match format {
+            TreeFormat::Codes => {
+                writeln!(
+                    out,
+                    "{:>8} {:>10} {:>6}  {:<24} token",
+                    "symbol", "weight", "length", "code"
+                )?;
+                for (symbol, code) in tree.codes().iter().enumerate() {
+                    let weight = header.table.weights[symbol];
+                    if weight > 0 {
+                        writeln!(
+                            out,
+                            "{:>8} {:>10} {:>6}  {:<24} {}",
+                            symbol,
+                            weight,
+                            code.len,
+                            code.to_string(),
+                            header.symbol_label(symbol as u32)
+                        )?;
                     }
                 }
-                bits_encoder.flush_current_byte();
-                let path_buf = Path::new(&path);
-                if let Some(path) = path_buf.parent() {
-                    let compress_file_path = path
-                        .join(path_buf.file_stem().unwrap().to_str().unwrap().to_owned() + ".huf");
-                    let mappings = serialize_huffman_mappings(&huff_freq).unwrap();
-                    let mapping_bytes = (mappings + "\n\n").into_bytes();
-                    println!("le === ==== {}", mapping_bytes.len());
-                    match fs::write(
-                        &compress_file_path,
-                        bits_encoder.bits_count.to_string().to_owned() + "\n",
-                    ) {
-                        Ok(_) => {
-                            println!("size {} written to file", &bits_encoder.bits_count);
-                        }
-                        Err(err) => {
-                            panic!("writing failed {}", err);
-                        }
-                    }
-
-                    let mut file = OpenOptions::new()
-                        .append(true)
-                        .open(&compress_file_path)
-                        .unwrap();
-
-                    match file.write_all(&mapping_bytes) {
-                        Ok(_) => {
-                            println!("mapping written to file");
-                        }
-                        Err(e) => {
-                            panic!("writing failed {}", e);
-                        }
-                    }
-
-                    match file.write_all(&bits_encoder.encode()) {
-                        Ok(_) => {
-                            println!("File written successfully")
-                        }
-                        Err(err) => {
-                            eprintln!("{err}")
-                        }
-                    }
-                };
-            } else {
-                panic!("something went wrong");
+            }
+            TreeFormat::Dot => {
+                write!(out, "{}", tree.to_dot(|symbol| header.symbol_label(symbol)))?
+            }
+            TreeFormat::Json => {
+                writeln!(out, "{}", tree.to_json(|symbol| header.symbol_json(symbol)))?
             }
         }
-        Err(err) => {
-            println!("{}", err);
-        }
-    }
+        Ok(())
+    })
 }
 
-fn get_priority_queue(huff_freq: &HashMap<char, u32>) -> BinaryHeap<HuffNode> {
-    let mut counter = 0;
-    let mut priority_queue = BinaryHeap::new();
-    for (key, value) in huff_freq {
-        println!("Key: {}, Value: {}", key, value);
-        priority_queue.push(HuffNode {
-            weight: *value,
-            element: Some(*key),
-            left: None,
-            right: None,
-            id: counter,
-        });
-        counter += 1;
-    }
-
-    priority_queue
+fn decode_range(path: &str, offset: u64, len: u64, mmap: bool) -> Result<(), FindError> {
+    let bytes = Input::open(path, mmap)?;
+    let decoded = decompress_range(&decrypted(&bytes)?, offset, len)?;
+    to_stdout(|out| out.write_all(&decoded))
 }
 
-fn get_huffman_tree_node(priority_queue: &mut BinaryHeap<HuffNode>) -> Option<HuffNode> {
-    let mut counter = priority_queue.len() as u32;
-    while priority_queue.len() > 1 {
-        let tmp1 = priority_queue.pop().unwrap();
-        let tmp2 = priority_queue.pop().unwrap();
-        println!("tmp1 = {} ,,, tmp2 = {}", tmp1, tmp2);
-        let tree_node = Option::from(Rc::new(RefCell::new(HuffNode::new(tmp1, tmp2, counter))));
-        priority_queue.push(tree_node.unwrap().borrow().to_owned());
-        counter += 1;
-    }
-
-    priority_queue.pop()
+/// `<stem><suffix>` next to `path`.
+fn sibling(path: &Path, suffix: &str) -> Result<PathBuf, FindError> {
+    let parent = path.parent().ok_or(FindError::NoParent)?;
+    let stem = path.file_stem().ok_or(FindError::NoParent)?;
+    Ok(parent.join(stem.to_string_lossy().into_owned() + suffix))
 }
 
-fn decode(path: &String) {
-    let path = Path::new(&path);
-    println!("file name {:?}", path.file_name().unwrap());
-    println!("file name {:?}", path.extension().unwrap());
-    match &path.parent() {
-        None => {
-            println!("Parent folder not found");
-        }
-        Some(parent_path) => {
-            let file_write_path = parent_path
-                .join(path.file_stem().unwrap().to_str().unwrap().to_owned() + "_decode" + ".txt");
-            let file = File::open(path).unwrap();
-            let mut reader = BufReader::new(file);
-            let mut buf_vec = Vec::new();
-            reader
-                .read_to_end(&mut buf_vec)
-                .expect("Error reading file");
-            println!("{}", buf_vec.len());
-            let huffman_decoder = HuffmanDecoder::new(buf_vec, file_write_path);
-            huffman_decoder.decode()
-        }
-    }
-}
-
-fn serialize_huffman_mappings(map: &HashMap<char, u32>) -> serde_json::error::Result<String> {
-    serde_json::to_string(map)
-}
-
-fn traverse_and_get_prefixes(node: TreeNodeRef) -> HashMap<char, Vec<bool>> {
-    let mut prefix_map = HashMap::new();
-    traverse_and_get_prefixes_int(&Some(node), &mut Vec::new(), &mut prefix_map);
-    prefix_map
+/// Compresses with `options`, or with every strategy of `level` for the
+/// smallest output and only the block size of `options`, and encrypts the
+/// result with `encrypt`.
+fn encode(
+    path: &str,
+    options: &CompressOptions,
+    level: Option<Level>,
+    encrypt: bool,
+    mmap: bool,
+) -> Result<(), FindError> {
+    let file_bytes = Input::open(path, mmap)?;
+    let compressed = match level {
+        Some(level) => compress_level(&file_bytes, level, options.block_size)?,
+        None => compress(&file_bytes, options)?,
+    };
+    let compressed = match encrypt {
+        true => encrypt::encrypt(&compressed, read_password()?.as_bytes())?,
+        false => compressed,
+    };
+
+    let compress_file_path = sibling(Path::new(path), ".huf")?;
+    fs::write(&compress_file_path, compressed)?;
+    info!("File written successfully to {:?}", compress_file_path);
+    Ok(())
 }
 
-fn traverse_and_get_prefixes_int(
-    node: &Option<TreeNodeRef>,
-    bits: &mut Vec<bool>,
-    map: &mut HashMap<char, Vec<bool>>,
-) {
-    if let Some(ref node_ref) = node {
-        let node_bor = node_ref;
-        if node_bor.is_leaf() {
-            // let str: String = bits.to_vec().iter().map(|x| if *x { '1' } else { '0' }).collect();
-            // println!("key: {}, value: {}", node_bor.element.unwrap(), str);
-            map.insert(node_bor.element.unwrap(), bits.to_vec());
-            return;
-        } else {
-            bits.push(false);
-            traverse_and_get_prefixes_int(&node_bor.left, bits, map);
-            bits.pop();
-            bits.push(true);
-            traverse_and_get_prefixes_int(&node_bor.right, bits, map);
-            bits.pop();
+/// Decodes `path` to `<stem>_decode.txt`, which is only written when the whole
+/// file decodes. With `recover` the damaged blocks of a blocked file are
+/// logged and left out instead of failing the decode.
+fn decode(path: &str, recover: bool, mmap: bool) -> Result<(), FindError> {
+    let path = Path::new(&path);
+    debug!("file name {:?}", path.file_name());
+    debug!("extension {:?}", path.extension());
+    let file_write_path = sibling(path, "_decode.txt")?;
+
+    let buf_vec = Input::open(path, mmap)?;
+    let bytes = decrypted(&buf_vec)?;
+    let decoded = if recover {
+        let recovered = compressor::recover(&bytes);
+        for damaged in &recovered.damaged {
+            warn!("Skipped damaged {}", damaged);
         }
+        recovered.data
     } else {
-        return;
-    }
-}
-
-fn get_frequency_from_string(s: &String) -> HashMap<char, u32> {
-    let mut huff_map = HashMap::new();
-
-    for character in s.chars() {
-        *huff_map.entry(character).or_insert(0) += 1
-    }
-
-    huff_map
+        decompress(&bytes)?
+    };
+    fs::write(&file_write_path, decoded)?;
+    info!("File written successfully to {:?}", file_write_path);
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use crate::*;
-    use std::env;
+    use std::path::PathBuf;
 
-    const PATH_TO_FILE: &str = "huffman.txt";
-    const PATH_TO_DECODE: &str = "huffman.huf";
-
-    const PATH_DECODED_FILE: &str = "huffman_decode.txt";
-
-    fn files_have_same_content(file1_path: &str, file2_path: &str) -> bool {
-        // Read the contents of both files
-        let file1_content = match fs::read_to_string(file1_path) {
-            Ok(content) => content,
-            Err(_) => return false, // Return false if unable to read file1
-        };
+    use tempfile::TempDir;
 
-        let file2_content = match fs::read_to_string(file2_path) {
-            Ok(content) => content,
-            Err(_) => return false, // Return false if unable to read file2
-        };
-        // Compare the contents of both files
-        file1_content == file2_content
+    /// Copies `name` from the crate root into a fresh temp dir, so the
+    /// `.huf` and `_decode.txt` files land there.
+    fn copy_to_temp_dir(name: &str) -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        fs::copy(name, &path).unwrap();
+        (dir, path)
     }
 
-    #[test]
-    fn check_frequency_of_some_english_characters() {
-        let current_dir = env::current_dir().expect("Failed to get current directory");
-
-        // Combine the current directory with the relative path
-        let file_path = current_dir.join(PATH_TO_FILE);
-        let file_str = fs::read_to_string(file_path).expect("It should be valid path");
+    fn assert_round_trip(name: &str, mmap: bool) {
+        let (dir, path) = copy_to_temp_dir(name);
+        let stem = path.file_stem().unwrap().to_str().unwrap();
 
-        let hash_map = get_frequency_from_string(&file_str);
+        encode(
+            path.to_str().unwrap(),
+            &CompressOptions::default(),
+            None,
+            false,
+            mmap,
+        )
+        .unwrap();
+        let compressed = dir.path().join(format!("{stem}.huf"));
+        decode(compressed.to_str().unwrap(), false, mmap).unwrap();
 
-        assert_eq!(hash_map.get(&'X'), Some(333).as_ref());
-        assert_eq!(hash_map.get(&'t'), Some(223000).as_ref())
+        let decoded = fs::read(dir.path().join(format!("{stem}_decode.txt"))).unwrap();
+        assert_eq!(decoded, fs::read(name).unwrap());
     }
 
     #[test]
-    fn check_frequency_of_some_non_english_characters() {
-        let current_dir = env::current_dir().expect("Failed to get current directory");
-
-        // Combine the current directory with the relative path
-        let file_path = current_dir.join(PATH_TO_FILE);
-        let file_str = fs::read_to_string(file_path).expect("It should be valid path");
-
-        let hash_map = get_frequency_from_string(&file_str);
-
-        assert_eq!(hash_map.get(&'â'), Some(56).as_ref());
-        assert_eq!(hash_map.get(&'À'), Some(5).as_ref());
+    fn encode_and_decode_should_generate_same_file_small() {
+        assert_round_trip("small.txt", false);
     }
 
     #[test]
-    fn check_frequency_of_some_other_characters() {
-        let current_dir = env::current_dir().expect("Failed to get current directory");
-
-        // Combine the current directory with the relative path
-        let file_path = current_dir.join(PATH_TO_FILE);
-        let file_str = fs::read_to_string(file_path).expect("It should be valid path");
-
-        let hash_map = get_frequency_from_string(&file_str);
-
-        assert_eq!(hash_map.get(&'\n'), Some(73589).as_ref());
-        assert_eq!(hash_map.get(&'$'), Some(2).as_ref());
+    fn encode_and_decode_should_generate_same_file() {
+        assert_round_trip("huffman.txt", true);
     }
 
     #[test]
-    fn encode_and_decode_should_generate_same_file_small() {
-        let current_dir = env::current_dir().expect("Failed to get current directory");
-
-        // Combine the current directory with the relative path
-        let file_path = current_dir.join("small.txt");
-
-        encode(&file_path.to_str().unwrap().to_string());
-        let file_decode_path = current_dir.join("small.huf");
-        decode(&file_decode_path.to_str().unwrap().to_string());
-        let file_decoded_path = current_dir.join("small_decode.txt");
-        assert_eq!(
-            files_have_same_content(
-                file_path.to_str().unwrap(),
-                file_decoded_path.to_str().unwrap()
-            ),
-            true
-        );
+    fn read_header_should_read_huf_files_and_compress_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("text.txt");
+        fs::write(&path, "abracadabra ".repeat(100)).unwrap();
+        let options = CompressOptions::default();
+        encode(path.to_str().unwrap(), &options, None, false, false).unwrap();
+        let compressed = dir.path().join("text.huf");
+
+        let from_text = read_header(path.to_str().unwrap(), &options).unwrap();
+        let from_huf = read_header(compressed.to_str().unwrap(), &options).unwrap();
+        assert_eq!(from_text.table.weights, from_huf.table.weights);
     }
 
     #[test]
-    fn encode_and_decode_should_generate_same_file() {
-        let current_dir = env::current_dir().expect("Failed to get current directory");
-
-        // Combine the current directory with the relative path
-        let file_path = current_dir.join(PATH_TO_FILE);
-
-        encode(&file_path.to_str().unwrap().to_string());
-        let file_decode_path = current_dir.join(PATH_TO_DECODE);
-        decode(&file_decode_path.to_str().unwrap().to_string());
-        let file_decoded_path = current_dir.join(PATH_DECODED_FILE);
-        assert_eq!(
-            files_have_same_content(
-                file_path.to_str().unwrap(),
-                file_decoded_path.to_str().unwrap()
-            ),
-            true
-        );
+    fn read_header_should_code_files_that_would_be_stored() {
+        let (_dir, path) = copy_to_temp_dir("small.txt");
+
+        let header = read_header(path.to_str().unwrap(), &CompressOptions::default()).unwrap();
+        assert!(header.tree().is_ok());
     }
 }
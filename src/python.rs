@@ -0,0 +1,132 @@
+//! Python module `compressor`, built by maturin with the `python` feature.
+//!
+//! Functions accept `str` or `bytes` wherever the Rust API takes input bytes;
+//! codec errors are raised as `ValueError`.
+
+use std::collections::BTreeMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+use crate::token::get_frequency_from_tokens;
+use crate::{get_frequency_from_string, CodecError, CompressOptions, HuffTree, TokenTable};
+
+impl From<CodecError> for PyErr {
+    fn from(err: CodecError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+#[derive(FromPyObject)]
+enum Data {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Data {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Data::Text(text) => text.as_bytes(),
+            Data::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+fn options(
+    tokenizer: &str,
+    order1: bool,
+    rle: bool,
+    block_size: Option<usize>,
+) -> PyResult<CompressOptions> {
+    Ok(CompressOptions {
+        tokenizer: tokenizer
+            .parse()
+            .map_err(|err: crate::TokenizerError| PyValueError::new_err(err.to_string()))?,
+        order1,
+        rle,
+        block_size,
+    })
+}
+
+/// Tokens become `str` keys when they are UTF-8 and `bytes` keys otherwise.
+fn token_key(py: Python<'_>, token: &[u8]) -> PyObject {
+    match std::str::from_utf8(token) {
+        Ok(token) => token.into_py(py),
+        Err(_) => PyBytes::new_bound(py, token).into_py(py),
+    }
+}
+
+/// compress(data, tokenizer="chars", order1=False, rle=False, block_size=None) -> bytes
+#[pyfunction]
+#[pyo3(signature = (data, tokenizer = "chars", order1 = false, rle = false, block_size = None))]
+fn compress<'py>(
+    py: Python<'py>,
+    data: Data,
+    tokenizer: &str,
+    order1: bool,
+    rle: bool,
+    block_size: Option<usize>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let options = options(tokenizer, order1, rle, block_size)?;
+    let compressed = py.allow_threads(|| crate::compress(data.as_bytes(), &options))?;
+    Ok(PyBytes::new_bound(py, &compressed))
+}
+
+/// decompress(data) -> bytes
+#[pyfunction]
+fn decompress<'py>(py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let decompressed = py.allow_threads(|| crate::decompress(data))?;
+    Ok(PyBytes::new_bound(py, &decompressed))
+}
+
+/// build_code_table(data, tokenizer="chars") -> dict mapping each token to
+/// its code as a string of "0" and "1".
+#[pyfunction]
+#[pyo3(signature = (data, tokenizer = "chars"))]
+fn build_code_table<'py>(
+    py: Python<'py>,
+    data: Data,
+    tokenizer: &str,
+) -> PyResult<Bound<'py, PyDict>> {
+    let options = options(tokenizer, false, false, None)?;
+    let tokens = options
+        .tokenizer
+        .split(data.as_bytes())
+        .map_err(CodecError::from)?;
+    let (table, _) = TokenTable::build(&tokens);
+    let tree = HuffTree::from_weights_or_single(&table.weights).ok_or(CodecError::TooFewSymbols)?;
+
+    let codes = PyDict::new_bound(py);
+    for (token, code) in table.tokens.iter().zip(tree.codes()) {
+        codes.set_item(token_key(py, token), code.to_string())?;
+    }
+    Ok(codes)
+}
+
+/// frequencies(data) -> dict counting every character of a `str`, or every
+/// byte value of `bytes`.
+#[pyfunction]
+fn frequencies(py: Python<'_>, data: Data) -> PyObject {
+    match data {
+        Data::Text(text) => get_frequency_from_string(&text).into_py(py),
+        Data::Bytes(bytes) => {
+            let tokens: Vec<&[u8]> = bytes.chunks(1).collect();
+            get_frequency_from_tokens(&tokens)
+                .into_iter()
+                .map(|(byte, count)| (byte[0], count))
+                .collect::<BTreeMap<u8, u32>>()
+                .into_py(py)
+        }
+    }
+}
+
+#[pymodule]
+fn compressor(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    m.add_function(wrap_pyfunction!(compress, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(build_code_table, m)?)?;
+    m.add_function(wrap_pyfunction!(frequencies, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,121 @@
+//! Decompressing [`Read`] adapter, see [`HufDecoder`].
+
+use std::io::{self, Read};
+
+use crate::BlockDecoder;
+
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Reads the decompressed data of the `.huf` stream in `R`, blocked or not.
+/// Data comes out a member at a time, so only one block of a blocked file is
+/// held in memory. A stream that ends in the middle of a member fails with
+/// `InvalidData`.
+pub struct HufDecoder<R: Read> {
+    inner: R,
+    decoder: Option<BlockDecoder>,
+    buffer: Box<[u8; READ_BUFFER_SIZE]>,
+    /// Decoded bytes not yet read, from `position` on.
+    output: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> HufDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            decoder: Some(BlockDecoder::new()),
+            buffer: Box::new([0; READ_BUFFER_SIZE]),
+            output: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for HufDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.position < self.output.len() || buf.is_empty() {
+                let len = buf.len().min(self.output.len() - self.position);
+                buf[..len].copy_from_slice(&self.output[self.position..self.position + len]);
+                self.position += len;
+                return Ok(len);
+            }
+            let Some(decoder) = &mut self.decoder else {
+                return Ok(0);
+            };
+
+            let len = match self.inner.read(&mut self.buffer[..]) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            self.output.clear();
+            self.position = 0;
+            if len == 0 {
+                self.decoder.take().unwrap().finish()?;
+            } else {
+                decoder.update(&self.buffer[..len], &mut self.output)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::write::HufEncoder;
+    use crate::{compress, CompressOptions, Tokenizer};
+
+    #[test]
+    fn decoder_should_read_blocked_and_plain_files() {
+        let mut input = std::fs::read("huffman.txt").unwrap();
+        input.truncate(100_000);
+        for block_size in [None, Some(1000)] {
+            let options = CompressOptions {
+                tokenizer: Tokenizer::Bytes,
+                block_size,
+                ..CompressOptions::default()
+            };
+            let compressed = compress(&input, &options).unwrap();
+
+            let mut decoded = Vec::new();
+            HufDecoder::new(&compressed[..])
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, input);
+        }
+    }
+
+    #[test]
+    fn encoder_and_decoder_should_round_trip_through_copy() {
+        let input = std::fs::read("small.txt").unwrap();
+        let mut encoder = HufEncoder::new(Vec::new(), &CompressOptions::default());
+        io::copy(&mut &input[..], &mut encoder).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoded = Vec::new();
+        io::copy(&mut HufDecoder::new(&compressed[..]), &mut decoded).unwrap();
+        assert_eq!(decoded, input);
+    }
+
+    #[test]
+    fn truncated_stream_should_fail() {
+        let compressed = compress(b"abracadabra", &CompressOptions::default()).unwrap();
+        let mut decoder = HufDecoder::new(&compressed[..compressed.len() / 2]);
+
+        let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,298 @@
+//! Decoding what is left of a damaged blocked file, see [`recover`].
+
+use core::fmt::{self, Display, Formatter};
+
+use crate::prelude::*;
+
+use crate::{BlockCheck, BlockIndex, CodecError, HuffmanDecoder};
+
+/// A range of the uncompressed data that could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Damaged {
+    pub offset: u64,
+    /// `None` when the damage runs to the end of a file whose size was lost
+    /// with its index.
+    pub len: Option<u64>,
+}
+
+impl Display for Damaged {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.len {
+            Some(len) => write!(f, "bytes {}..{}", self.offset, self.offset + len),
+            None => write!(f, "bytes {}.. to the end", self.offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recovered {
+    /// The data of every block that passed its check, in order, with the
+    /// damaged ranges left out.
+    pub data: Vec<u8>,
+    pub damaged: Vec<Damaged>,
+}
+
+enum Member {
+    Block {
+        check: Option<BlockCheck>,
+        data: Vec<u8>,
+        len: usize,
+    },
+    Index(BlockIndex),
+}
+
+fn member_at(bytes: &[u8]) -> Result<Member, CodecError> {
+    let decoder = HuffmanDecoder::new(bytes);
+    let (mappings, _, _) = decoder.read_mappings()?;
+    if BlockIndex::is_index(&mappings) {
+        return Ok(Member::Index(BlockIndex::from_json(&mappings)?));
+    }
+    let check = BlockCheck::from_json(&mappings)?;
+    let (data, len) = decoder
+        .decode_member()?
+        .ok_or(CodecError::InvalidHeader("unexpected index"))?;
+    Ok(Member::Block { check, data, len })
+}
+
+/// The first member from `from` on that is the index, or a block whose
+/// check passes and that starts at or after `expected`. Members start with
+/// the bit count line followed by `{"`, so only those spots are tried.
+fn resync(bytes: &[u8], from: usize, expected: u64) -> Option<(usize, Member)> {
+    let mut at = from;
+    while let Some(found) = bytes.get(at..)?.windows(3).position(|w| w == b"\n{\"") {
+        let line = at + found;
+        at = line + 1;
+        let digits = bytes[..line]
+            .iter()
+            .rev()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        let start = line - digits;
+        if digits == 0 || start < from {
+            continue;
+        }
+        let Ok(member) = member_at(&bytes[start..]) else {
+            continue;
+        };
+        let resumes = match &member {
+            Member::Index(_) => true,
+            Member::Block { check, .. } => check.is_some_and(|check| check.offset >= expected),
+        };
+        if resumes {
+            return Some((start, member));
+        }
+    }
+    None
+}
+
+/// Decodes every intact block of the blocked `.huf` file `bytes` and reports
+/// the ranges of the blocks that are damaged. After a damaged block decoding
+/// picks up at the next member header whose block passes its
+/// [`BlockCheck`], so one bad bit costs one block. The sizes of damaged
+/// blocks come from the offsets of the blocks after them, and from the index
+/// for damage at the end of the file.
+///
+/// A file without blocks is one block: it is recovered whole or not at all.
+pub fn recover(bytes: &[u8]) -> Recovered {
+    let mut recovered = Recovered::default();
+    let mut size = BlockIndex::read(bytes)
+        .ok()
+        .flatten()
+        .map(|index| index.size);
+    // Uncompressed offset the next block should start at.
+    let mut expected = 0;
+    let mut position = 0;
+    let mut next = member_at(bytes);
+    let mut ended = false;
+    // Files written with checks have them on every block, so once one is
+    // seen a block without one has a damaged header.
+    let mut checked = false;
+    while position < bytes.len() {
+        match next {
+            Ok(Member::Block { check: None, .. }) if checked => {
+                next = Err(CodecError::InvalidHeader("missing block check"));
+            }
+            Ok(Member::Block { check, data, len }) => {
+                checked |= check.is_some();
+                let offset = check.map_or(expected, |check| check.offset);
+                if offset > expected {
+                    recovered.damaged.push(Damaged {
+                        offset: expected,
+                        len: Some(offset - expected),
+                    });
+                }
+                expected = offset + data.len() as u64;
+                recovered.data.extend_from_slice(&data);
+                position += len;
+                next = member_at(&bytes[position..]);
+            }
+            Ok(Member::Index(index)) => {
+                size = Some(index.size);
+                ended = true;
+                break;
+            }
+            Err(_) => match resync(bytes, position + 1, expected) {
+                Some((start, member)) => {
+                    position = start;
+                    next = Ok(member);
+                }
+                None => break,
+            },
+        }
+    }
+    match size {
+        Some(size) if size > expected => recovered.damaged.push(Damaged {
+            offset: expected,
+            len: Some(size - expected),
+        }),
+        None if !ended && position < bytes.len() => recovered.damaged.push(Damaged {
+            offset: expected,
+            len: None,
+        }),
+        _ => {}
+    }
+    recovered
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{compress, decompress, CompressOptions, Tokenizer};
+
+    const BLOCK: usize = 2000;
+
+    fn input() -> Vec<u8> {
+        std::fs::read("huffman.txt").unwrap()[..10_000].to_vec()
+    }
+
+    fn blocked(input: &[u8]) -> Vec<u8> {
+        let options = CompressOptions {
+            tokenizer: Tokenizer::Bytes,
+            block_size: Some(BLOCK),
+            ..CompressOptions::default()
+        };
+        compress(input, &options).unwrap()
+    }
+
+    /// Byte position of the payload of block `n`.
+    fn payload_of(compressed: &[u8], n: usize) -> usize {
+        let index = BlockIndex::read(compressed).unwrap().unwrap();
+        let start = index.blocks[n].1 as usize;
+        start
+            + HuffmanDecoder::new(&compressed[start..])
+                .read_mappings()
+                .unwrap()
+                .1
+    }
+
+    /// `recovered` is `input` with the damaged ranges cut out.
+    fn assert_consistent(input: &[u8], recovered: &Recovered) {
+        let mut expected = Vec::new();
+        let mut at = 0;
+        for damaged in &recovered.damaged {
+            let offset = damaged.offset as usize;
+            expected.extend_from_slice(&input[at..offset]);
+            at = damaged.len.map_or(input.len(), |len| offset + len as usize);
+        }
+        expected.extend_from_slice(&input[at..]);
+        assert_eq!(recovered.data, expected);
+    }
+
+    #[test]
+    fn intact_files_should_recover_whole() {
+        let input = input();
+        let recovered = recover(&blocked(&input));
+        assert_eq!(recovered.data, input);
+        assert!(recovered.damaged.is_empty());
+    }
+
+    #[test]
+    fn flipped_bit_should_cost_one_block() {
+        let input = input();
+        let mut compressed = blocked(&input);
+        let payload = payload_of(&compressed, 2);
+        compressed[payload + 10] ^= 0x10;
+
+        assert!(matches!(
+            decompress(&compressed),
+            Err(CodecError::Checksum | CodecError::InvalidPayload)
+        ));
+        let recovered = recover(&compressed);
+        assert_eq!(
+            recovered.damaged,
+            [Damaged {
+                offset: 2 * BLOCK as u64,
+                len: Some(BLOCK as u64)
+            }]
+        );
+        assert_consistent(&input, &recovered);
+    }
+
+    #[test]
+    fn damaged_header_and_index_should_be_skipped() {
+        let input = input();
+        let mut compressed = blocked(&input);
+        // Break the JSON of block 1 and the offset line pointing at the index.
+        let header = BlockIndex::read(&compressed).unwrap().unwrap().blocks[1].1 as usize;
+        let brace = header
+            + compressed[header..]
+                .iter()
+                .position(|b| *b == b'{')
+                .unwrap();
+        compressed[brace] = b'(';
+        let last = compressed.len() - 2;
+        compressed[last] = b'x';
+
+        let recovered = recover(&compressed);
+        assert_eq!(
+            recovered.damaged,
+            [Damaged {
+                offset: BLOCK as u64,
+                len: Some(BLOCK as u64)
+            }]
+        );
+        assert_consistent(&input, &recovered);
+    }
+
+    #[test]
+    fn truncated_file_should_report_the_unknown_end() {
+        let input = input();
+        let compressed = blocked(&input);
+        let truncated = &compressed[..payload_of(&compressed, 3) + 5];
+
+        let recovered = recover(truncated);
+        assert_eq!(
+            recovered.damaged,
+            [Damaged {
+                offset: 3 * BLOCK as u64,
+                len: None
+            }]
+        );
+        assert_eq!(recovered.data, &input[..3 * BLOCK]);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn recovered_data_should_match_the_input(
+            flips in prop::collection::vec((any::<prop::sample::Index>(), 0u8..8), 1..4),
+        ) {
+            let input = input();
+            let mut compressed = blocked(&input);
+            // The index has no check, a flip in its size would move the end.
+            let last_line = compressed[..compressed.len() - 1]
+                .rsplit(|b| *b == b'\n')
+                .next()
+                .unwrap();
+            let index: usize = std::str::from_utf8(last_line).unwrap().parse().unwrap();
+            for (at, bit) in flips {
+                let at = at.index(index);
+                compressed[at] ^= 1 << bit;
+            }
+            assert_consistent(&input, &recover(&compressed));
+        }
+    }
+}
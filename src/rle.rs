@@ -0,0 +1,126 @@
+use core::iter::repeat;
+
+use crate::prelude::*;
+
+use crate::CodecError;
+
+/// Run symbols appended after the tokens when run-length coding is on. Run
+/// symbol `j` repeats the previous literal symbol `2^j` more times, so any
+/// repeat count is written as the run symbols of its set bits.
+pub const RUN_SYMBOLS: u32 = 32;
+
+/// Shorter repeats are left as literals, a single run symbol would not save
+/// anything over them.
+const MIN_REPEATS: usize = 2;
+
+/// Most repeats one group of run symbols can write, longer runs take several.
+const MAX_REPEATS: u64 = (1 << RUN_SYMBOLS) - 1;
+
+/// Replaces every run of one symbol by the symbol followed by run symbols
+/// `run_base + j` for the set bits `j` of the repeat count, repeated for
+/// counts of `2^32` and more.
+pub fn encode_runs(symbols: &[u32], run_base: u32) -> Vec<u32> {
+    let mut encoded = Vec::with_capacity(symbols.len());
+    let mut start = 0;
+    while start < symbols.len() {
+        let symbol = symbols[start];
+        let end = symbols[start..]
+            .iter()
+            .position(|s| *s != symbol)
+            .map_or(symbols.len(), |len| start + len);
+
+        encoded.push(symbol);
+        push_repeats(&mut encoded, symbol, (end - start - 1) as u64, run_base);
+        start = end;
+    }
+    encoded
+}
+
+fn push_repeats(encoded: &mut Vec<u32>, symbol: u32, mut repeats: u64, run_base: u32) {
+    while repeats >= MIN_REPEATS as u64 {
+        let group = repeats.min(MAX_REPEATS);
+        for j in (0..RUN_SYMBOLS).rev() {
+            if (group >> j) & 1 == 1 {
+                encoded.push(run_base + j);
+            }
+        }
+        repeats -= group;
+    }
+    encoded.extend(repeat(symbol).take(repeats as usize));
+}
+
+/// Expands the runs of [`encode_runs`] back into `expanded` symbols, the
+/// count recorded in the header. Runs that would go past it fail before they
+/// are written, so the output is bounded by the header, which the decoder
+/// caps at [`MAX_BLOCK_SIZE`](crate::block::MAX_BLOCK_SIZE) symbols.
+pub fn decode_runs(symbols: &[u32], run_base: u32, expanded: u64) -> Result<Vec<u32>, CodecError> {
+    let mut decoded = Vec::with_capacity(symbols.len());
+    let mut previous = None;
+    for symbol in symbols {
+        let repeats = match symbol.checked_sub(run_base) {
+            Some(j) if j < RUN_SYMBOLS => 1u64 << j,
+            Some(_) => return Err(CodecError::InvalidPayload),
+            None => 1,
+        };
+        if decoded.len() as u64 + repeats > expanded {
+            return Err(CodecError::InvalidPayload);
+        }
+        if *symbol >= run_base {
+            let literal = previous.ok_or(CodecError::InvalidPayload)?;
+            decoded.extend(repeat(literal).take(repeats as usize));
+        } else {
+            decoded.push(*symbol);
+            previous = Some(*symbol);
+        }
+    }
+    if decoded.len() as u64 != expanded {
+        return Err(CodecError::InvalidPayload);
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_should_be_written_as_powers_of_two() {
+        let mut symbols = vec![1, 0, 0];
+        symbols.extend(repeat(2).take(14));
+        symbols.push(1);
+
+        // 2 repeated 13 more times: 8 + 4 + 1
+        let encoded = encode_runs(&symbols, 3);
+        assert_eq!(encoded, [1, 0, 0, 2, 3 + 3, 3 + 2, 3, 1]);
+        assert_eq!(decode_runs(&encoded, 3, 18).unwrap(), symbols);
+    }
+
+    #[test]
+    fn runs_longer_than_one_group_should_be_split() {
+        let repeats = (1 << 33) + 5;
+        let mut encoded = Vec::new();
+        push_repeats(&mut encoded, 0, repeats, 1);
+
+        // Two full groups of 2^32 - 1, then 7.
+        assert_eq!(encoded.len(), 2 * 32 + 3);
+        let written: u64 = encoded.iter().map(|symbol| 1 << (symbol - 1)).sum();
+        assert_eq!(written, repeats);
+    }
+
+    #[test]
+    fn run_without_literal_should_be_rejected() {
+        assert!(decode_runs(&[3, 0], 3, 2).is_err());
+    }
+
+    #[test]
+    fn runs_past_the_expanded_count_should_be_rejected() {
+        // 0 followed by 2^31 repeats from one run symbol.
+        let encoded = [0, 1 + 31];
+        assert!(matches!(
+            decode_runs(&encoded, 1, 1000),
+            Err(CodecError::InvalidPayload)
+        ));
+        assert!(decode_runs(&[0, 1 + 2], 1, 6).is_err());
+        assert_eq!(decode_runs(&[0, 1 + 2], 1, 5).unwrap(), [0; 5]);
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt::{Display, Formatter};
+
+use serde_json::{json, Value};
+
+use crate::{compress, compress_coded, token_to_json, CodecError, CompressOptions, HuffmanDecoder};
+
+/// How one symbol of the coded alphabet was counted and coded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolStats {
+    pub symbol: u32,
+    /// `None` for a run symbol, see [`crate::rle`].
+    pub token: Option<Vec<u8>>,
+    /// See [`Header::symbol_label`](crate::Header::symbol_label).
+    pub label: String,
+    pub count: u32,
+    /// Length of the order-0 code.
+    pub code_length: u8,
+}
+
+/// Size and entropy report for compressing one input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub original_size: u64,
+    pub compressed_size: u64,
+    /// Bit count line and mappings in front of the payload.
+    pub header_size: u64,
+    pub payload_bits: u64,
+    /// Number of coded symbols, after run-length coding if it is on.
+    pub symbol_count: u64,
+    /// Shannon entropy of the symbol frequencies in bits per symbol.
+    pub entropy: f64,
+    /// Payload bits per coded symbol.
+    pub average_code_length: f64,
+    /// Symbols that occur at least once, in symbol order.
+    pub symbols: Vec<SymbolStats>,
+}
+
+impl Stats {
+    /// Compressed size over original size.
+    pub fn ratio(&self) -> f64 {
+        if self.original_size == 0 {
+            return 0.0;
+        }
+        self.compressed_size as f64 / self.original_size as f64
+    }
+
+    pub fn to_json(&self) -> Value {
+        let symbols = self
+            .symbols
+            .iter()
+            .map(|s| {
+                json!({
+                    "symbol": s.symbol,
+                    "token": s.token.as_deref().map_or(Value::Null, token_to_json),
+                    "count": s.count,
+                    "code_length": s.code_length,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "original_size": self.original_size,
+            "compressed_size": self.compressed_size,
+            "header_size": self.header_size,
+            "payload_bits": self.payload_bits,
+            "ratio": self.ratio(),
+            "symbol_count": self.symbol_count,
+            "entropy": self.entropy,
+            "average_code_length": self.average_code_length,
+            "symbols": symbols,
+        })
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "original size        {} bytes", self.original_size)?;
+        writeln!(f, "compressed size      {} bytes", self.compressed_size)?;
+        writeln!(f, "header overhead      {} bytes", self.header_size)?;
+        writeln!(f, "ratio                {:.4}", self.ratio())?;
+        writeln!(f, "symbols              {}", self.symbol_count)?;
+        writeln!(f, "entropy              {:.4} bits/symbol", self.entropy)?;
+        writeln!(
+            f,
+            "average code length  {:.4} bits/symbol",
+            self.average_code_length
+        )?;
+        writeln!(f)?;
+        writeln!(f, "{:>8} {:>10} {:>6}  token", "symbol", "count", "length")?;
+        for s in &self.symbols {
+            writeln!(
+                f,
+                "{:>8} {:>10} {:>6}  {}",
+                s.symbol, s.count, s.code_length, s.label
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Shannon entropy in bits per symbol of a distribution given by counts.
+pub fn entropy(counts: &[u32]) -> f64 {
+    let total: u64 = counts.iter().map(|c| *c as u64).sum();
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Huffman codes `input` with `options`, as a single member even when
+/// [`compress`] would store it or split it into blocks, and
+/// reports on the result without writing anything. Empty input has nothing
+/// to code and reports zero symbols and the stored member `compress` writes.
+pub fn analyze(input: &[u8], options: &CompressOptions) -> Result<Stats, CodecError> {
+    if input.is_empty() {
+        let stored = compress(input, options)?.len() as u64;
+        return Ok(Stats {
+            original_size: 0,
+            compressed_size: stored,
+            header_size: stored,
+            payload_bits: 0,
+            symbol_count: 0,
+            entropy: 0.0,
+            average_code_length: 0.0,
+            symbols: Vec::new(),
+        });
+    }
+    let compressed = compress_coded(input, options)?;
+    let (header, header_size, payload_bits) = HuffmanDecoder::new(&compressed).get_mappings()?;
+    let weights = &header.table.weights;
+    let lengths = header.tree()?.code_lengths();
+
+    let symbol_count = weights.iter().map(|w| *w as u64).sum();
+    let symbols = weights
+        .iter()
+        .zip(&lengths)
+        .enumerate()
+        .filter(|(_, (count, _))| **count > 0)
+        .map(|(symbol, (count, code_length))| SymbolStats {
+            symbol: symbol as u32,
+            token: header.table.tokens.get(symbol).cloned(),
+            label: header.symbol_label(symbol as u32),
+            count: *count,
+            code_length: *code_length,
+        })
+        .collect();
+
+    Ok(Stats {
+        original_size: input.len() as u64,
+        compressed_size: compressed.len() as u64,
+        header_size: header_size as u64,
+        payload_bits,
+        symbol_count,
+        entropy: entropy(weights),
+        average_code_length: payload_bits as f64 / symbol_count as f64,
+        symbols,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entropy_should_be_log2_of_equally_likely_symbols() {
+        assert_eq!(entropy(&[3, 3, 3, 3]), 2.0);
+        assert_eq!(entropy(&[7, 0]), 0.0);
+    }
+
+    #[test]
+    fn huffman_should_stay_within_one_bit_of_entropy() {
+        let stats = analyze(b"abracadabra", &CompressOptions::default()).unwrap();
+
+        assert_eq!(stats.original_size, 11);
+        assert_eq!(stats.payload_bits, 23);
+        assert_eq!(
+            stats.compressed_size,
+            stats.header_size + stats.payload_bits.div_ceil(8)
+        );
+        assert_eq!(stats.symbols.len(), 5);
+        assert_eq!(stats.symbols[0].token.as_deref(), Some(&b"a"[..]));
+        assert_eq!(stats.symbols[0].code_length, 1);
+        assert!(stats.average_code_length >= stats.entropy);
+        assert!(stats.average_code_length < stats.entropy + 1.0);
+        assert_eq!(stats.to_json()["symbols"][4]["token"], "r");
+    }
+
+    #[test]
+    fn empty_input_should_report_zero_symbols() {
+        let stats = analyze(b"", &CompressOptions::default()).unwrap();
+
+        assert_eq!(stats.symbol_count, 0);
+        assert_eq!(stats.payload_bits, 0);
+        assert!(stats.symbols.is_empty());
+        assert_eq!(stats.ratio(), 0.0);
+        assert_eq!(stats.compressed_size, stats.header_size);
+    }
+}
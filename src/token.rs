@@ -0,0 +1,297 @@
+use alloc::collections::BTreeMap;
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
+
+use crate::prelude::*;
+
+/// Words seen fewer times than this are spelled out byte by byte.
+pub const DEFAULT_MIN_WORD_COUNT: u32 = 2;
+
+/// How the input is cut into the symbols that get Huffman codes.
+///
+/// Every tokenizer splits the input into consecutive slices, so decoding is
+/// always a plain concatenation of the decoded tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tokenizer {
+    /// One symbol per byte; accepts any input.
+    Bytes,
+    /// One symbol per UTF-8 character; the input has to be valid UTF-8.
+    #[default]
+    Chars,
+    /// Runs of alphanumeric (or non-ASCII) bytes become one symbol, every other
+    /// byte is a symbol of its own. Words occurring fewer than `min_count`
+    /// times are escaped into their single bytes so that the token table only
+    /// carries words that pay for themselves.
+    Words { min_count: u32 },
+    /// Fixed chunks of `n` bytes; the last chunk may be shorter.
+    NGrams(usize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenizerError {
+    InvalidUtf8,
+    Unknown(String),
+}
+
+impl Display for TokenizerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizerError::InvalidUtf8 => {
+                write!(
+                    f,
+                    "Input is not valid UTF-8, use the bytes tokenizer instead"
+                )
+            }
+            TokenizerError::Unknown(name) => write!(
+                f,
+                "Unknown tokenizer '{}', expected bytes, chars, words[:MIN_COUNT] or ngram:N",
+                name
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TokenizerError {}
+
+impl Display for Tokenizer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Tokenizer::Bytes => write!(f, "bytes"),
+            Tokenizer::Chars => write!(f, "chars"),
+            Tokenizer::Words { min_count } => write!(f, "words:{}", min_count),
+            Tokenizer::NGrams(n) => write!(f, "ngram:{}", n),
+        }
+    }
+}
+
+impl FromStr for Tokenizer {
+    type Err = TokenizerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || TokenizerError::Unknown(s.to_owned());
+        let (name, param) = match s.split_once(':') {
+            None => (s, None),
+            Some((name, param)) => (name, Some(param.parse::<u32>().map_err(|_| unknown())?)),
+        };
+        match (name, param) {
+            ("bytes", None) => Ok(Tokenizer::Bytes),
+            ("chars", None) => Ok(Tokenizer::Chars),
+            ("words", None) => Ok(Tokenizer::Words {
+                min_count: DEFAULT_MIN_WORD_COUNT,
+            }),
+            ("words", Some(min_count)) => Ok(Tokenizer::Words { min_count }),
+            ("ngram", Some(n)) if n > 0 => Ok(Tokenizer::NGrams(n as usize)),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+impl Tokenizer {
+    /// Rejects `NGrams(0)`, which no tokenizer name parses to but the enum
+    /// can still be built with.
+    pub fn check(&self) -> Result<(), TokenizerError> {
+        match self {
+            Tokenizer::NGrams(0) => Err(TokenizerError::Unknown(self.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn split<'a>(&self, input: &'a [u8]) -> Result<Vec<&'a [u8]>, TokenizerError> {
+        self.check()?;
+        match self {
+            Tokenizer::Bytes => Ok(input.chunks(1).collect()),
+            Tokenizer::Chars => {
+                let s = core::str::from_utf8(input).map_err(|_| TokenizerError::InvalidUtf8)?;
+                Ok(s.char_indices()
+                    .map(|(i, c)| &input[i..i + c.len_utf8()])
+                    .collect())
+            }
+            Tokenizer::Words { min_count } => Ok(split_words(input, *min_count)),
+            Tokenizer::NGrams(n) => Ok(input.chunks(*n).collect()),
+        }
+    }
+
+    /// Moves `at` back to the nearest point where `input` can be cut without
+    /// splitting a token: a character boundary for `Chars`, a multiple of
+    /// `n` for `NGrams(n)`. Word tokens may be cut anywhere, the halves are
+    /// just coded as two shorter words. `NGrams(0)` leaves `at` alone, see
+    /// [`check`](Self::check).
+    pub fn boundary(&self, input: &[u8], at: usize) -> usize {
+        match self {
+            Tokenizer::Chars => (at.saturating_sub(3)..=at)
+                .rev()
+                .find(|i| input.get(*i).map_or(true, |b| b & 0xc0 != 0x80))
+                .unwrap_or(at),
+            Tokenizer::NGrams(n) => at - at % (*n).max(1),
+            Tokenizer::Bytes | Tokenizer::Words { .. } => at,
+        }
+    }
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || !b.is_ascii()
+}
+
+fn split_words(input: &[u8], min_count: u32) -> Vec<&[u8]> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while start < input.len() {
+        let end = if is_word_byte(input[start]) {
+            input[start..]
+                .iter()
+                .position(|b| !is_word_byte(*b))
+                .map_or(input.len(), |len| start + len)
+        } else {
+            start + 1
+        };
+        pieces.push(&input[start..end]);
+        start = end;
+    }
+
+    let counts = get_frequency_from_tokens(&pieces);
+    let mut tokens = Vec::with_capacity(pieces.len());
+    for piece in pieces {
+        if piece.len() > 1 && counts[piece] < min_count {
+            tokens.extend(piece.chunks(1));
+        } else {
+            tokens.push(piece);
+        }
+    }
+    tokens
+}
+
+pub fn get_frequency_from_tokens<'a>(tokens: &[&'a [u8]]) -> BTreeMap<&'a [u8], u32> {
+    let mut freq = BTreeMap::new();
+    for token in tokens {
+        *freq.entry(*token).or_insert(0) += 1
+    }
+    freq
+}
+
+/// Distinct tokens in sorted order together with their counts. A symbol is
+/// the index of its token in this table.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TokenTable {
+    pub tokens: Vec<Vec<u8>>,
+    /// Weight of every symbol. With run-length coding the run symbols follow
+    /// the tokens, so this can be longer than `tokens`.
+    pub weights: Vec<u32>,
+}
+
+impl TokenTable {
+    /// Counts `tokens` and maps each of them to its symbol.
+    pub fn build(tokens: &[&[u8]]) -> (Self, Vec<u32>) {
+        let freq = get_frequency_from_tokens(tokens);
+        let index: BTreeMap<&[u8], u32> = freq
+            .keys()
+            .enumerate()
+            .map(|(symbol, token)| (*token, symbol as u32))
+            .collect();
+        let symbols = tokens.iter().map(|token| index[token]).collect();
+        let table = Self {
+            tokens: freq.keys().map(|token| token.to_vec()).collect(),
+            weights: freq.values().copied().collect(),
+        };
+        (table, symbols)
+    }
+
+    /// Replaces the weights with the counts of `symbols` over an alphabet of
+    /// `alphabet_size` symbols.
+    pub fn recount(&mut self, symbols: &[u32], alphabet_size: usize) {
+        self.weights = vec![0; alphabet_size];
+        for symbol in symbols {
+            self.weights[*symbol as usize] += 1;
+        }
+    }
+
+    /// Concatenates the tokens of `symbols`; `None` if one of them is not a
+    /// token.
+    pub fn detokenize(&self, symbols: &[u32]) -> Option<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(symbols.len());
+        for symbol in symbols {
+            buffer.extend_from_slice(self.tokens.get(*symbol as usize)?);
+        }
+        Some(buffer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_to_strings(tokenizer: Tokenizer, input: &str) -> Vec<&str> {
+        tokenizer
+            .split(input.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(|token| std::str::from_utf8(token).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn tokenizer_should_round_trip_through_its_name() {
+        for tokenizer in [
+            Tokenizer::Bytes,
+            Tokenizer::Chars,
+            Tokenizer::Words { min_count: 3 },
+            Tokenizer::NGrams(2),
+        ] {
+            assert_eq!(tokenizer.to_string().parse(), Ok(tokenizer));
+        }
+        assert!("ngram:0".parse::<Tokenizer>().is_err());
+        assert!("lines".parse::<Tokenizer>().is_err());
+    }
+
+    #[test]
+    fn words_below_min_count_should_be_escaped_to_bytes() {
+        let tokens = split_to_strings(Tokenizer::Words { min_count: 2 }, "the cat, the hat");
+        assert_eq!(
+            tokens,
+            ["the", " ", "c", "a", "t", ",", " ", "the", " ", "h", "a", "t"]
+        );
+    }
+
+    #[test]
+    fn ngrams_should_keep_the_short_tail() {
+        let tokens = split_to_strings(Tokenizer::NGrams(3), "abcdefgh");
+        assert_eq!(tokens, ["abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn zero_length_ngrams_should_be_rejected() {
+        let tokenizer = Tokenizer::NGrams(0);
+        assert_eq!(
+            tokenizer.split(b"abc"),
+            Err(TokenizerError::Unknown("ngram:0".to_owned()))
+        );
+        assert_eq!(tokenizer.boundary(b"abc", 2), 2);
+        for block_size in [None, Some(2)] {
+            let options = crate::CompressOptions {
+                tokenizer,
+                block_size,
+                ..Default::default()
+            };
+            assert!(matches!(
+                crate::compress(b"", &options),
+                Err(crate::CodecError::Tokenizer(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn chars_should_reject_invalid_utf8() {
+        assert_eq!(
+            Tokenizer::Chars.split(&[0x61, 0xff]),
+            Err(TokenizerError::InvalidUtf8)
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use alloc::collections::VecDeque;
+use core::fmt::{self, Display, Formatter, Write};
+
+use serde_json::{json, Value};
+
+use crate::prelude::*;
+
+/// A code packed into the low `len` bits of `value`, first bit in the most
+/// significant position, so it can be written with a single
+/// [`BitWriter::write_bits`](crate::BitWriter::write_bits) call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Code {
+    pub value: u64,
+    pub len: u8,
+}
+
+impl Code {
+    /// Bit `i` of the code, counted from the first one.
+    pub fn bit(&self, i: u8) -> bool {
+        (self.value >> (self.len - 1 - i)) & 1 == 1
+    }
+
+    fn push(self, bit: bool) -> Self {
+        Self {
+            value: (self.value << 1) | bit as u64,
+            len: self.len + 1,
+        }
+    }
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for i in 0..self.len {
+            write!(f, "{}", if self.bit(i) { '1' } else { '0' })?;
+        }
+        Ok(())
+    }
+}
+
+/// Index of a node inside a [`HuffTree`], doubling as its id.
+pub type NodeId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HuffNode {
+    pub weight: u32,
+    pub element: Option<u32>,
+    pub left: Option<NodeId>,
+    pub right: Option<NodeId>,
+}
+
+impl HuffNode {
+    fn leaf(element: u32, weight: u32) -> Self {
+        Self {
+            weight,
+            element: Some(element),
+            left: None,
+            right: None,
+        }
+    }
+}
+
+/// Huffman tree stored as a flat array with the root as the last node. Trees
+/// built from weights keep leaf `i` at index `i`, followed by the internal
+/// nodes in the order they were merged. No shared pointers are involved, so
+/// the tree is `Send + Sync` and can be handed to several decoding threads by
+/// reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffTree {
+    nodes: Vec<HuffNode>,
+}
+
+impl Display for HuffTree {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let root = self.node(self.root());
+        write!(
+            f,
+            "HuffTree {{ weight: {}, nodes: {}, root id: {} }}",
+            root.weight,
+            self.nodes.len(),
+            self.root()
+        )
+    }
+}
+
+impl HuffTree {
+    /// Builds the tree with the two-queue method: leaves are sorted once and
+    /// merged nodes come out in non-decreasing weight, so picking the lighter
+    /// front of the two queues is enough and construction is linear after the
+    /// sort.
+    ///
+    /// Ties go to leaves first, then to the larger symbol, then to the older
+    /// merged node, which keeps the codes identical to the previous heap-based
+    /// construction.
+    ///
+    /// `weights[i]` is the weight of symbol `i`. Symbols of weight zero keep
+    /// their leaf but are left out of the tree and get no code. Returns `None`
+    /// when fewer than two symbols have a weight, or when the weights add up
+    /// to more than [`u32::MAX`], the weight of the root.
+    pub fn from_weights(weights: &[u32]) -> Option<Self> {
+        if weights.iter().filter(|weight| **weight > 0).count() < 2
+            || weights.iter().map(|weight| *weight as u64).sum::<u64>() > u32::MAX as u64
+        {
+            return None;
+        }
+
+        let mut nodes: Vec<HuffNode> = weights
+            .iter()
+            .enumerate()
+            .map(|(element, weight)| HuffNode::leaf(element as u32, *weight))
+            .collect();
+        let mut leaves: Vec<NodeId> = (0..nodes.len()).filter(|i| weights[*i] > 0).collect();
+        leaves.sort_by(|a, b| {
+            nodes[*a]
+                .weight
+                .cmp(&nodes[*b].weight)
+                .then_with(|| nodes[*b].element.cmp(&nodes[*a].element))
+        });
+
+        let mut leaves = VecDeque::from(leaves);
+        let mut merged: VecDeque<NodeId> = VecDeque::with_capacity(leaves.len() - 1);
+        while leaves.len() + merged.len() > 1 {
+            let left = Self::pop_lightest(&nodes, &mut leaves, &mut merged);
+            let right = Self::pop_lightest(&nodes, &mut leaves, &mut merged);
+            nodes.push(HuffNode {
+                weight: nodes[left].weight + nodes[right].weight,
+                element: None,
+                left: Some(left),
+                right: Some(right),
+            });
+            merged.push_back(nodes.len() - 1);
+        }
+
+        Some(Self { nodes })
+    }
+
+    /// Like [`from_weights`](Self::from_weights), but a lone symbol hangs off
+    /// the root as its left child and gets the one-bit code `0`, so input that
+    /// repeats a single token can still be coded. Returns `None` when no
+    /// symbol has a weight and otherwise where `from_weights` does.
+    pub fn from_weights_or_single(weights: &[u32]) -> Option<Self> {
+        let mut weighted = (0..weights.len()).filter(|i| weights[*i] > 0);
+        match (weighted.next(), weighted.next()) {
+            (Some(symbol), None) => {
+                let mut nodes: Vec<HuffNode> = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(element, weight)| HuffNode::leaf(element as u32, *weight))
+                    .collect();
+                nodes.push(HuffNode {
+                    weight: weights[symbol],
+                    element: None,
+                    left: Some(symbol),
+                    right: None,
+                });
+                Some(Self { nodes })
+            }
+            _ => Self::from_weights(weights),
+        }
+    }
+
+    fn pop_lightest(
+        nodes: &[HuffNode],
+        leaves: &mut VecDeque<NodeId>,
+        merged: &mut VecDeque<NodeId>,
+    ) -> NodeId {
+        match (leaves.front(), merged.front()) {
+            (Some(leaf), Some(node)) if nodes[*node].weight < nodes[*leaf].weight => {
+                merged.pop_front().unwrap()
+            }
+            (Some(_), _) => leaves.pop_front().unwrap(),
+            (None, _) => merged.pop_front().unwrap(),
+        }
+    }
+
+    /// Rebuilds the tree of a canonical code from the length of every symbol's
+    /// code (see [`canonical_codes`]). Only the shape is known, so all weights
+    /// are zero. Symbols of length zero keep a leaf outside the tree, as in
+    /// [`from_weights`](Self::from_weights). Returns `None` for fewer than two
+    /// codes or lengths that do not form a prefix code.
+    pub fn from_code_lengths(lengths: &[u8]) -> Option<Self> {
+        let mut codes: Vec<(Code, u32)> = canonical_codes(lengths)
+            .into_iter()
+            .enumerate()
+            .filter(|(symbol, _)| lengths[*symbol] > 0)
+            .map(|(symbol, code)| (code, symbol as u32))
+            .collect();
+        if codes.len() < 2 {
+            return None;
+        }
+        // Left-align the codes so that they sort like bit strings.
+        codes.sort_by_key(|(code, _)| (code.value << (64 - code.len as u32), code.len));
+
+        let mut nodes: Vec<HuffNode> = (0..lengths.len())
+            .filter(|symbol| lengths[*symbol] == 0)
+            .map(|symbol| HuffNode::leaf(symbol as u32, 0))
+            .collect();
+        nodes.reserve(2 * codes.len() - 1);
+        Self::build_from_codes(&mut nodes, &codes, 0).ok()?;
+        Some(Self { nodes })
+    }
+
+    /// Pushes the subtree holding `codes` (sorted, all sharing their first
+    /// `depth` bits) after its children. An empty range is a hole in an
+    /// incomplete code and yields no node.
+    fn build_from_codes(
+        nodes: &mut Vec<HuffNode>,
+        codes: &[(Code, u32)],
+        depth: u8,
+    ) -> Result<Option<NodeId>, ()> {
+        match codes {
+            [] => return Ok(None),
+            [(code, symbol)] if code.len == depth => {
+                nodes.push(HuffNode::leaf(*symbol, 0));
+                return Ok(Some(nodes.len() - 1));
+            }
+            _ => {}
+        }
+        if codes.iter().any(|(code, _)| code.len <= depth) {
+            return Err(());
+        }
+        let split = codes.partition_point(|(code, _)| !code.bit(depth));
+        let left = Self::build_from_codes(nodes, &codes[..split], depth + 1)?;
+        let right = Self::build_from_codes(nodes, &codes[split..], depth + 1)?;
+        nodes.push(HuffNode {
+            weight: 0,
+            element: None,
+            left,
+            right,
+        });
+        Ok(Some(nodes.len() - 1))
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.nodes.len() - 1
+    }
+
+    pub fn node(&self, id: NodeId) -> &HuffNode {
+        &self.nodes[id]
+    }
+
+    /// Follows one edge from `id`; `false` is the left child.
+    pub fn child(&self, id: NodeId, bit: bool) -> Option<NodeId> {
+        let node = &self.nodes[id];
+        if bit {
+            node.right
+        } else {
+            node.left
+        }
+    }
+
+    /// Walks from the root along `bits` until a leaf is reached. Returns
+    /// `None` if the bits run out first or lead to a missing child.
+    pub fn decode_symbol(&self, bits: &mut impl Iterator<Item = bool>) -> Option<u32> {
+        let mut id = self.root();
+        loop {
+            id = self.child(id, bits.next()?)?;
+            if let Some(element) = self.nodes[id].element {
+                return Some(element);
+            }
+        }
+    }
+
+    /// Number of leaves, including those of symbols without a code.
+    pub fn symbol_count(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| node.element.is_some())
+            .count()
+    }
+
+    /// Length of every symbol's code, indexed by symbol.
+    pub fn code_lengths(&self) -> Vec<u8> {
+        self.codes().iter().map(|code| code.len).collect()
+    }
+
+    /// Code of every symbol, indexed by symbol.
+    pub fn codes(&self) -> Vec<Code> {
+        let mut codes = vec![Code::default(); self.symbol_count()];
+        self.traverse_and_get_codes(self.root(), Code::default(), &mut codes);
+        codes
+    }
+
+    /// Graphviz DOT rendering: internal nodes show their weight, leaves are
+    /// boxes with `label(symbol)` and the weight, edges carry their bit.
+    pub fn to_dot(&self, label: impl Fn(u32) -> String) -> String {
+        let mut dot = String::from("digraph huffman {\n    node [shape=circle];\n");
+        let mut stack = vec![self.root()];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            match node.element {
+                Some(element) => writeln!(
+                    dot,
+                    "    n{} [shape=box, label=\"{}\\n{}\"];",
+                    id,
+                    dot_escape(&label(element)),
+                    node.weight
+                ),
+                None => writeln!(dot, "    n{} [label=\"{}\"];", id, node.weight),
+            }
+            .unwrap();
+            for (bit, child) in [(0, node.left), (1, node.right)] {
+                if let Some(child) = child {
+                    writeln!(dot, "    n{} -> n{} [label=\"{}\"];", id, child, bit).unwrap();
+                    stack.push(child);
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Nested JSON rendering. Every node has its `id` and `weight`, internal
+    /// nodes a `left` (bit 0) and `right` (bit 1) child, leaves their
+    /// `symbol`, `token(symbol)` and `code`.
+    pub fn to_json(&self, token: impl Fn(u32) -> Value) -> Value {
+        self.node_to_json(self.root(), Code::default(), &token)
+    }
+
+    fn node_to_json(&self, id: NodeId, code: Code, token: &impl Fn(u32) -> Value) -> Value {
+        let node = &self.nodes[id];
+        match node.element {
+            Some(element) => json!({
+                "id": id,
+                "weight": node.weight,
+                "symbol": element,
+                "token": token(element),
+                "code": code.to_string(),
+            }),
+            None => {
+                let mut object = json!({ "id": id, "weight": node.weight });
+                for (bit, key, child) in [(false, "left", node.left), (true, "right", node.right)] {
+                    if let Some(child) = child {
+                        object[key] = self.node_to_json(child, code.push(bit), token);
+                    }
+                }
+                object
+            }
+        }
+    }
+
+    fn traverse_and_get_codes(&self, id: NodeId, code: Code, codes: &mut [Code]) {
+        let node = &self.nodes[id];
+        if let Some(element) = node.element {
+            codes[element as usize] = code;
+            return;
+        }
+        for (bit, child) in [(false, node.left), (true, node.right)] {
+            if let Some(child) = child {
+                self.traverse_and_get_codes(child, code.push(bit), codes);
+            }
+        }
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Canonical Huffman code for the given code lengths: codes are handed out
+/// in order of length, then symbol, each one the previous code plus one,
+/// shifted left whenever the length grows. Symbols with length zero get an
+/// empty code. Only the lengths have to be stored to rebuild the same codes.
+pub fn canonical_codes(lengths: &[u8]) -> Vec<Code> {
+    let mut order: Vec<usize> = (0..lengths.len()).filter(|s| lengths[*s] > 0).collect();
+    order.sort_by_key(|symbol| (lengths[*symbol], *symbol));
+
+    let mut codes = vec![Code::default(); lengths.len()];
+    let mut code: u64 = 0;
+    let mut previous_length = 0;
+    for (i, symbol) in order.into_iter().enumerate() {
+        let length = lengths[symbol];
+        if i > 0 {
+            code = (code + 1) << (length - previous_length);
+        }
+        previous_length = length;
+        codes[symbol] = Code {
+            value: code,
+            len: length,
+        };
+    }
+    codes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_should_be_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<HuffTree>();
+    }
+
+    #[test]
+    fn tree_should_need_at_least_two_symbols() {
+        assert_eq!(HuffTree::from_weights(&[]), None);
+        assert_eq!(HuffTree::from_weights(&[4]), None);
+        assert_eq!(HuffTree::from_weights(&[4, 0, 0]), None);
+    }
+
+    #[test]
+    fn single_symbol_should_get_a_one_bit_code() {
+        let tree = HuffTree::from_weights_or_single(&[0, 4, 0]).unwrap();
+        assert_eq!(tree.codes()[1].to_string(), "0");
+        assert_eq!(tree.code_lengths(), vec![0, 1, 0]);
+        assert_eq!(tree.decode_symbol(&mut [false].into_iter()), Some(1));
+        assert_eq!(tree.decode_symbol(&mut [true].into_iter()), None);
+        assert_eq!(HuffTree::from_weights_or_single(&[0, 0]), None);
+    }
+
+    #[test]
+    fn symbols_without_weight_should_get_no_code() {
+        let tree = HuffTree::from_weights(&[3, 0, 1, 0]).unwrap();
+        assert_eq!(tree.code_lengths(), [1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn codes_should_match_heap_based_construction() {
+        // "abracadabra": a, b, c, d, r
+        let tree = HuffTree::from_weights(&[5, 2, 1, 1, 2]).unwrap();
+        let codes = tree.codes();
+
+        assert_eq!(tree.node(tree.root()).weight, 11);
+        assert_eq!(codes[0].to_string(), "0");
+        assert_eq!(codes[1].to_string(), "111");
+        assert_eq!(codes[4].to_string(), "110");
+        assert_eq!(codes[2].to_string(), "101");
+        assert_eq!(codes[3].to_string(), "100");
+        assert_eq!(
+            codes[1],
+            Code {
+                value: 0b111,
+                len: 3
+            }
+        );
+    }
+
+    #[test]
+    fn canonical_codes_should_follow_length_then_symbol_order() {
+        let codes = canonical_codes(&[2, 1, 3, 3, 0]);
+
+        assert_eq!(codes[1].to_string(), "0");
+        assert_eq!(codes[0].to_string(), "10");
+        assert_eq!(codes[2].to_string(), "110");
+        assert_eq!(codes[3].to_string(), "111");
+        assert_eq!(codes[4].len, 0);
+    }
+
+    #[test]
+    fn tree_from_code_lengths_should_decode_canonical_codes() {
+        let lengths = HuffTree::from_weights(&[5, 2, 1, 1, 2])
+            .unwrap()
+            .code_lengths();
+        let tree = HuffTree::from_code_lengths(&lengths).unwrap();
+
+        assert_eq!(tree.code_lengths(), lengths);
+        assert_eq!(tree.codes(), canonical_codes(&lengths));
+        assert_eq!(HuffTree::from_code_lengths(&[1, 1, 1]), None);
+    }
+
+    #[test]
+    fn symbols_without_a_code_should_keep_their_index() {
+        let tree = HuffTree::from_code_lengths(&[1, 0, 1]).unwrap();
+
+        assert_eq!(tree.symbol_count(), 3);
+        assert_eq!(tree.code_lengths(), [1, 0, 1]);
+        assert_eq!(tree.decode_symbol(&mut [true].into_iter()), Some(2));
+        assert_eq!(
+            HuffTree::from_code_lengths(&[0, 2, 2, 0, 1, 0])
+                .unwrap()
+                .code_lengths(),
+            [0, 2, 2, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn exports_should_show_weights_symbols_and_edge_bits() {
+        let tree = HuffTree::from_weights(&[2, 1, 1]).unwrap();
+        let labels = ["a", "\"", "c"];
+
+        let dot = tree.to_dot(|symbol| labels[symbol as usize].to_owned());
+        assert!(dot.starts_with("digraph huffman {"));
+        assert!(dot.contains("n4 [label=\"4\"];"));
+        assert!(dot.contains("n1 [shape=box, label=\"\\\"\\n1\"];"));
+        assert!(dot.contains("n4 -> n0 [label=\"0\"];"));
+
+        let json = tree.to_json(|symbol| labels[symbol as usize].into());
+        assert_eq!(json["weight"], 4);
+        assert_eq!(json["left"]["token"], "a");
+        assert_eq!(json["right"]["right"]["code"], "11");
+    }
+}
@@ -0,0 +1,37 @@
+//! JavaScript bindings, built with the `wasm` feature for
+//! `wasm32-unknown-unknown` and wrapped by wasm-bindgen.
+//!
+//! Input and output are `Uint8Array`s; omitted options fall back to
+//! [`CompressOptions::default`], and codec errors are thrown as `Error`.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{CompressOptions, Tokenizer};
+
+/// compress(input, tokenizer?, order1?, rle?, blockSize?) -> Uint8Array
+#[wasm_bindgen]
+pub fn compress(
+    input: &[u8],
+    tokenizer: Option<String>,
+    order1: Option<bool>,
+    rle: Option<bool>,
+    #[wasm_bindgen(js_name = blockSize)] block_size: Option<usize>,
+) -> Result<Vec<u8>, JsError> {
+    let tokenizer = match tokenizer {
+        Some(name) => name.parse::<Tokenizer>()?,
+        None => Default::default(),
+    };
+    let options = CompressOptions {
+        tokenizer,
+        order1: order1.unwrap_or_default(),
+        rle: rle.unwrap_or_default(),
+        block_size,
+    };
+    Ok(crate::compress(input, &options)?)
+}
+
+/// decompress(input) -> Uint8Array
+#[wasm_bindgen]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, JsError> {
+    Ok(crate::decompress(input)?)
+}
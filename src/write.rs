@@ -0,0 +1,201 @@
+//! Compressing [`Write`] adapter, see [`HufEncoder`].
+
+use std::io::{self, Write};
+
+use crate::{BlockEncoder, CompressOptions};
+
+/// Writes the blocked `.huf` form of everything written to it into `W`, one
+/// block at a time: a filled block goes out with the next write or flush.
+///
+/// Call [`finish`](HufEncoder::finish) to write the last block and the block
+/// index and get `W` back. Dropping the encoder finishes it too, but errors
+/// are lost then.
+pub struct HufEncoder<W: Write> {
+    inner: Option<W>,
+    encoder: Option<BlockEncoder>,
+    /// Compressed bytes not yet taken by `inner`, from `position` on.
+    output: Vec<u8>,
+    position: usize,
+}
+
+impl<W: Write> HufEncoder<W> {
+    /// Blocks are `options.block_size` bytes, see [`BlockEncoder::new`].
+    pub fn new(inner: W, options: &CompressOptions) -> Self {
+        Self {
+            inner: Some(inner),
+            encoder: Some(BlockEncoder::new(options)),
+            output: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Writes the last block and the block index, leaving the encoder unable
+    /// to take more data.
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        self.drain()?;
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish(&mut self.output)?;
+            self.drain()?;
+        }
+        self.get_mut().flush()
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    /// Writes out the pending output. Progress is kept across errors, so a
+    /// retry picks up where the failed write stopped.
+    fn drain(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        while self.position < self.output.len() {
+            match inner.write(&self.output[self.position..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => self.position += written,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.output.clear();
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HufEncoder<W> {
+    /// Fails only before taking any of `buf`: output left over from earlier
+    /// calls is written out first, and the blocks `buf` completes are
+    /// written by the next call.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.drain()?;
+        let Some(encoder) = &mut self.encoder else {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "write after finish",
+            ));
+        };
+        let len = buf.len().min(encoder.block_size());
+        encoder.update(&buf[..len], &mut self.output)?;
+        Ok(len)
+    }
+
+    /// Flushes the completed blocks. The current block stays buffered,
+    /// cutting it short would change the output.
+    fn flush(&mut self) -> io::Result<()> {
+        self.drain()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for HufEncoder<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufWriter;
+
+    use super::*;
+    use crate::{compress, decompress, Tokenizer};
+
+    fn options() -> CompressOptions {
+        CompressOptions {
+            tokenizer: Tokenizer::Bytes,
+            block_size: Some(1000),
+            ..CompressOptions::default()
+        }
+    }
+
+    #[test]
+    fn encoder_should_match_compress() {
+        let mut input = std::fs::read("huffman.txt").unwrap();
+        input.truncate(100_000);
+        let mut encoder = HufEncoder::new(Vec::new(), &options());
+        for chunk in input.chunks(777) {
+            encoder.write_all(chunk).unwrap();
+        }
+
+        assert_eq!(
+            encoder.finish().unwrap(),
+            compress(&input, &options()).unwrap()
+        );
+    }
+
+    /// Takes at most 100 bytes per write and fails the third write.
+    struct FailOnce {
+        written: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for FailOnce {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls == 3 {
+                return Err(io::Error::new(io::ErrorKind::Other, "failed once"));
+            }
+            let len = buf.len().min(100);
+            self.written.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_write_should_be_retried_without_losing_output() {
+        let mut input = std::fs::read("huffman.txt").unwrap();
+        input.truncate(10_000);
+        let inner = FailOnce {
+            written: Vec::new(),
+            calls: 0,
+        };
+        let mut encoder = HufEncoder::new(inner, &options());
+        let mut rest = &input[..];
+        let mut errors = 0;
+        while !rest.is_empty() {
+            match encoder.write(rest) {
+                Ok(len) => rest = &rest[len..],
+                Err(_) => errors += 1,
+            }
+        }
+
+        assert_eq!(errors, 1);
+        assert_eq!(
+            encoder.finish().unwrap().written,
+            compress(&input, &options()).unwrap()
+        );
+    }
+
+    #[test]
+    fn encoder_should_compose_with_writers() {
+        let value = serde_json::json!({ "name": "huffman", "weights": [5, 2, 1, 1, 2] });
+        let mut compressed = Vec::new();
+        {
+            let encoder = HufEncoder::new(&mut compressed, &CompressOptions::default());
+            let mut writer = BufWriter::new(encoder);
+            serde_json::to_writer(&mut writer, &value).unwrap();
+            // Dropping finishes the encoder.
+        }
+
+        let decompressed = decompress(&compressed).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&decompressed).unwrap(),
+            value
+        );
+    }
+}
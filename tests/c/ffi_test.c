@@ -0,0 +1,103 @@
+/* Exercises include/compressor.h against the static library; run by tests/ffi.rs. */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "compressor.h"
+
+#define CHECK(cond)                                                    \
+  do {                                                                 \
+    if (!(cond)) {                                                     \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+              #cond);                                                  \
+      exit(1);                                                         \
+    }                                                                  \
+  } while (0)
+
+static const char TEXT[] =
+    "It was the best of times, it was the worst of times, it was the age of "
+    "wisdom, it was the age of foolishness, it was the epoch of belief.";
+
+static void append(HufBuffer *all, HufBuffer part) {
+  all->data = realloc(all->data, all->len + part.len + 1);
+  CHECK(all->data != NULL);
+  if (part.len > 0) {
+    memcpy(all->data + all->len, part.data, part.len);
+  }
+  all->len += part.len;
+  huf_buffer_free(part);
+}
+
+static void one_shot_round_trip(void) {
+  HufOptions options = {"words", true, false, 0};
+  HufBuffer compressed, decompressed;
+
+  CHECK(huf_compress((const uint8_t *)TEXT, strlen(TEXT), &options, &compressed) == HUF_OK);
+  CHECK(huf_decompress(compressed.data, compressed.len, &decompressed) == HUF_OK);
+  CHECK(decompressed.len == strlen(TEXT));
+  CHECK(memcmp(decompressed.data, TEXT, decompressed.len) == 0);
+  huf_buffer_free(compressed);
+  huf_buffer_free(decompressed);
+
+  CHECK(huf_compress((const uint8_t *)"aaaa", 4, NULL, &compressed) == HUF_OK);
+  CHECK(huf_decompress(compressed.data, compressed.len, &decompressed) == HUF_OK);
+  CHECK(decompressed.len == 4 && memcmp(decompressed.data, "aaaa", 4) == 0);
+  huf_buffer_free(compressed);
+  huf_buffer_free(decompressed);
+}
+
+static void streaming_round_trip(void) {
+  HufOptions options = {NULL, false, false, 32};
+  HufEncoder *encoder = huf_encoder_new(&options);
+  HufDecoder *decoder = huf_decoder_new();
+  HufBuffer compressed = {NULL, 0}, decompressed = {NULL, 0}, part;
+  size_t i;
+
+  CHECK(encoder != NULL && decoder != NULL);
+  for (i = 0; i < strlen(TEXT); i += 10) {
+    size_t len = strlen(TEXT) - i < 10 ? strlen(TEXT) - i : 10;
+    CHECK(huf_encoder_update(encoder, (const uint8_t *)TEXT + i, len, &part) == HUF_OK);
+    append(&compressed, part);
+  }
+  CHECK(huf_encoder_finish(encoder, &part) == HUF_OK);
+  append(&compressed, part);
+  CHECK(huf_encoder_update(encoder, (const uint8_t *)TEXT, 1, &part) == HUF_INVALID_ARGUMENT);
+  huf_encoder_free(encoder);
+
+  for (i = 0; i < compressed.len; i += 7) {
+    size_t len = compressed.len - i < 7 ? compressed.len - i : 7;
+    CHECK(huf_decoder_update(decoder, compressed.data + i, len, &part) == HUF_OK);
+    append(&decompressed, part);
+  }
+  CHECK(huf_decoder_finish(decoder) == HUF_OK);
+  huf_decoder_free(decoder);
+
+  CHECK(decompressed.len == strlen(TEXT));
+  CHECK(memcmp(decompressed.data, TEXT, decompressed.len) == 0);
+  free(compressed.data);
+  free(decompressed.data);
+}
+
+static void errors(void) {
+  HufOptions bad_tokenizer = {"lines", false, false, 0};
+  HufBuffer out;
+  const uint8_t garbage[] = "12\nnot json\n\nxx";
+
+  CHECK(huf_compress((const uint8_t *)TEXT, 4, &bad_tokenizer, &out) == HUF_TOKENIZER);
+  CHECK(out.data == NULL && out.len == 0);
+  CHECK(huf_encoder_new(&bad_tokenizer) == NULL);
+  CHECK(huf_decompress(garbage, sizeof garbage - 1, &out) == HUF_INVALID_HEADER);
+  CHECK(huf_decompress(NULL, 4, &out) == HUF_INVALID_ARGUMENT);
+  CHECK(huf_compress((const uint8_t *)TEXT, 4, NULL, NULL) == HUF_INVALID_ARGUMENT);
+  CHECK(strcmp(huf_status_message(HUF_INVALID_HEADER), "invalid header") == 0);
+  CHECK(strcmp(huf_status_message(42), "unknown status") == 0);
+}
+
+int main(void) {
+  one_shot_round_trip();
+  streaming_round_trip();
+  errors();
+  puts("ffi ok");
+  return 0;
+}
@@ -0,0 +1,128 @@
+//! Builds `tests/c/ffi_test.c` against the static library and runs it, and
+//! checks the hand-written `include/compressor.h` against `src/ffi.rs`.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::ptr;
+
+use compressor::ffi::*;
+
+/// Names of the functions declared or defined in `source`, the identifiers
+/// starting with `huf_` that are directly followed by their arguments.
+fn functions(source: &str) -> BTreeSet<&str> {
+    source
+        .match_indices("huf_")
+        .filter_map(|(at, _)| {
+            let name = &source[at..];
+            let len = name.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')?;
+            name[len..].starts_with('(').then(|| &name[..len])
+        })
+        .collect()
+}
+
+#[test]
+fn header_should_match_the_exports() {
+    let header = fs::read_to_string("include/compressor.h").unwrap();
+    let ffi = fs::read_to_string("src/ffi.rs").unwrap();
+    let exported: BTreeSet<_> = ffi
+        .split("extern \"C\" fn ")
+        .skip(1)
+        .filter_map(|rest| rest.split('(').next())
+        .collect();
+    assert_eq!(functions(&header), exported);
+
+    let statuses = [
+        HufStatus::Ok,
+        HufStatus::InvalidArgument,
+        HufStatus::Tokenizer,
+        HufStatus::TooFewSymbols,
+        HufStatus::InvalidHeader,
+        HufStatus::InvalidPayload,
+        HufStatus::Io,
+        HufStatus::Panic,
+        HufStatus::Encrypted,
+        HufStatus::Authentication,
+        HufStatus::Checksum,
+    ];
+    for status in statuses {
+        // Exhaustive, so a new status fails to compile until it is added.
+        let name = match status {
+            HufStatus::Ok => "HUF_OK",
+            HufStatus::InvalidArgument => "HUF_INVALID_ARGUMENT",
+            HufStatus::Tokenizer => "HUF_TOKENIZER",
+            HufStatus::TooFewSymbols => "HUF_TOO_FEW_SYMBOLS",
+            HufStatus::InvalidHeader => "HUF_INVALID_HEADER",
+            HufStatus::InvalidPayload => "HUF_INVALID_PAYLOAD",
+            HufStatus::Io => "HUF_IO",
+            HufStatus::Panic => "HUF_PANIC",
+            HufStatus::Encrypted => "HUF_ENCRYPTED",
+            HufStatus::Authentication => "HUF_AUTHENTICATION",
+            HufStatus::Checksum => "HUF_CHECKSUM",
+        };
+        let line = format!("  {} = {},", name, status as i32);
+        assert!(header.lines().any(|l| l == line), "missing `{}`", line);
+    }
+    assert_eq!(header.matches("  HUF_").count(), statuses.len());
+}
+
+#[test]
+fn finish_without_output_should_keep_the_encoder() {
+    unsafe {
+        let encoder = huf_encoder_new(ptr::null());
+        assert!(!encoder.is_null());
+        assert_eq!(
+            huf_encoder_finish(encoder, ptr::null_mut()),
+            HufStatus::InvalidArgument
+        );
+        let mut out = HufBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        assert_eq!(huf_encoder_finish(encoder, &mut out), HufStatus::Ok);
+        huf_buffer_free(out);
+        huf_encoder_free(encoder);
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn c_harness_should_pass() {
+    // The staticlib comes from the compressor-ffi crate, see ffi/Cargo.toml. It
+    // gets a target dir of its own so as not to wait on the running build.
+    let tmp = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let built = Command::new(env!("CARGO"))
+        .args(["build", "--package", "compressor-ffi", "--target-dir"])
+        .arg(tmp.join("ffi"))
+        .status()
+        .unwrap();
+    assert!(built.success(), "building the staticlib failed");
+    let library = tmp.join("ffi/debug/libcompressor_ffi.a");
+
+    let harness = tmp.join("ffi_test");
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let compiled = Command::new(&cc)
+        .args([
+            "-std=c99",
+            "-Wall",
+            "-Werror",
+            "-Iinclude",
+            "tests/c/ffi_test.c",
+        ])
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&harness)
+        .status()
+        .unwrap_or_else(|err| panic!("cannot run {} (set CC to a C compiler): {}", cc, err));
+    assert!(compiled.success(), "compiling the C harness failed");
+
+    let output = Command::new(&harness).output().unwrap();
+    assert!(
+        output.status.success(),
+        "C harness failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ffi ok\n");
+}
@@ -1 +1,262 @@
+//! Runs the `compressor` binary on copies of the sample files in temp dirs.
 
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use tempfile::TempDir;
+
+fn compressor(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_compressor"))
+        .args(args)
+        .env_remove("RUST_LOG")
+        .env_remove("HUF_PASSWORD")
+        .output()
+        .unwrap()
+}
+
+/// Runs the binary with `password` on stdin.
+fn compressor_with_password(args: &[&str], password: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_compressor"))
+        .args(args)
+        .env_remove("RUST_LOG")
+        .env_remove("HUF_PASSWORD")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    writeln!(child.stdin.take().unwrap(), "{password}").unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn arg(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+/// A temp dir holding a copy of `name` from the crate root.
+fn copy_to_temp_dir(name: &str) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(name);
+    fs::copy(name, &path).unwrap();
+    (dir, path)
+}
+
+#[test]
+fn encode_and_decode_should_restore_the_file() {
+    let (dir, path) = copy_to_temp_dir("huffman.txt");
+
+    let encoded = compressor(&[arg(&path)]);
+    assert!(encoded.status.success());
+    let compressed = dir.path().join("huffman.huf");
+    assert!(fs::metadata(&compressed).unwrap().len() < fs::metadata(&path).unwrap().len());
+
+    let decoded = compressor(&["-d", arg(&compressed)]);
+    assert!(decoded.status.success());
+    assert_eq!(
+        fs::read(dir.path().join("huffman_decode.txt")).unwrap(),
+        fs::read(&path).unwrap()
+    );
+}
+
+#[test]
+fn corrupt_files_should_fail_without_output() {
+    let (dir, path) = copy_to_temp_dir("huffman.txt");
+    assert!(compressor(&[arg(&path)]).status.success());
+    let compressed = dir.path().join("huffman.huf");
+    let mut damaged = fs::read(&compressed).unwrap();
+    let middle = damaged.len() / 2;
+    damaged[middle] ^= 0x01;
+    fs::write(&compressed, damaged).unwrap();
+
+    let decoded = compressor(&["-d", arg(&compressed)]);
+    assert!(!decoded.status.success());
+    assert!(String::from_utf8_lossy(&decoded.stderr).contains("the file is damaged"));
+    assert!(!dir.path().join("huffman_decode.txt").exists());
+}
+
+#[test]
+fn coding_options_should_round_trip() {
+    for options in [
+        &["-t", "bytes"][..],
+        &["-t", "words", "--order1"],
+        &["-t", "ngram:2", "--rle", "--mmap"],
+    ] {
+        let (dir, path) = copy_to_temp_dir("small.txt");
+
+        assert!(compressor(&[&[arg(&path)], options].concat())
+            .status
+            .success());
+        let compressed = dir.path().join("small.huf");
+        assert!(compressor(&["-d", arg(&compressed)]).status.success());
+        assert_eq!(
+            fs::read(dir.path().join("small_decode.txt")).unwrap(),
+            fs::read(&path).unwrap(),
+            "{options:?}"
+        );
+    }
+}
+
+#[test]
+fn higher_levels_should_never_give_larger_files() {
+    let (dir, path) = copy_to_temp_dir("small.txt");
+    let compressed = dir.path().join("small.huf");
+
+    let mut previous = u64::MAX;
+    for level in ["-1", "-3", "-6", "--level=9"] {
+        assert!(compressor(&[level, arg(&path)]).status.success());
+        let len = fs::metadata(&compressed).unwrap().len();
+        assert!(len <= previous, "{level}");
+        previous = len;
+
+        assert!(compressor(&["-d", arg(&compressed)]).status.success());
+        assert_eq!(
+            fs::read(dir.path().join("small_decode.txt")).unwrap(),
+            fs::read(&path).unwrap()
+        );
+    }
+}
+
+#[test]
+fn level_should_conflict_with_coding_options() {
+    let output = compressor(&["-9", "--order1", "small.txt"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+}
+
+#[test]
+fn encrypted_files_should_need_the_password() {
+    let (dir, path) = copy_to_temp_dir("small.txt");
+    let compressed = dir.path().join("small.huf");
+    let decoded = dir.path().join("small_decode.txt");
+
+    let encrypted = Command::new(env!("CARGO_BIN_EXE_compressor"))
+        .args(["--encrypt", arg(&path)])
+        .env("HUF_PASSWORD", "correct horse")
+        .output()
+        .unwrap();
+    assert!(encrypted.status.success());
+    let sealed = fs::read(&compressed).unwrap();
+    let plain = fs::read(&path).unwrap();
+    assert!(!sealed.windows(plain.len()).any(|w| w == plain));
+
+    let wrong = compressor_with_password(&["-d", arg(&compressed)], "battery staple");
+    assert!(!wrong.status.success());
+    assert!(String::from_utf8_lossy(&wrong.stderr).contains("wrong password or tampered file"));
+    assert!(!decoded.exists());
+
+    let missing = compressor(&["-d", arg(&compressed)]);
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("The password is empty"));
+    assert!(!decoded.exists());
+
+    let right = compressor_with_password(&["-d", arg(&compressed)], "correct horse");
+    assert!(right.status.success());
+    assert_eq!(fs::read(&decoded).unwrap(), plain);
+}
+
+#[test]
+fn range_should_write_part_of_a_blocked_file_to_stdout() {
+    let (dir, path) = copy_to_temp_dir("huffman.txt");
+
+    assert!(compressor(&["--block-size", "4096", arg(&path)])
+        .status
+        .success());
+    let compressed = dir.path().join("huffman.huf");
+    let range = compressor(&["-d", "--range", "10000:500", arg(&compressed)]);
+
+    assert!(range.status.success());
+    assert_eq!(range.stdout, fs::read(&path).unwrap()[10000..10500]);
+}
+
+#[test]
+fn recover_should_skip_damaged_blocks() {
+    let (dir, path) = copy_to_temp_dir("huffman.txt");
+    let compressed = dir.path().join("huffman.huf");
+    let decoded = dir.path().join("huffman_decode.txt");
+
+    assert!(compressor(&["--block-size", "4096", arg(&path)])
+        .status
+        .success());
+    let mut damaged = fs::read(&compressed).unwrap();
+    let middle = damaged.len() / 2;
+    damaged[middle] ^= 0x01;
+    fs::write(&compressed, damaged).unwrap();
+
+    let failed = compressor(&["-d", arg(&compressed)]);
+    assert!(!failed.status.success());
+    assert!(String::from_utf8_lossy(&failed.stderr).contains("the file is damaged"));
+    assert!(!decoded.exists());
+
+    let recovered = compressor(&["-d", "--recover", arg(&compressed)]);
+    assert!(recovered.status.success());
+    assert!(String::from_utf8_lossy(&recovered.stderr).contains("Skipped damaged bytes"));
+    let original = fs::read(&path).unwrap();
+    let decoded = fs::read(&decoded).unwrap();
+    assert!(decoded.len() < original.len());
+    assert!(decoded.len() >= original.len() - 4096);
+}
+
+#[test]
+fn stats_should_print_json() {
+    let stats = compressor(&["stats", "--json", "small.txt"]);
+
+    assert!(stats.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&stats.stdout).unwrap();
+    assert_eq!(
+        report["original_size"],
+        fs::metadata("small.txt").unwrap().len()
+    );
+}
+
+#[test]
+fn stats_should_stop_quietly_when_the_reader_goes_away() {
+    // Every word becomes a symbol, the report is far larger than a pipe buffer.
+    let mut child = Command::new(env!("CARGO_BIN_EXE_compressor"))
+        .args(["stats", "-t", "words:1", "huffman.txt"])
+        .env_remove("RUST_LOG")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut head = [0; 16];
+    child.stdout.take().unwrap().read_exact(&mut head).unwrap();
+    let stats = child.wait_with_output().unwrap();
+
+    assert!(stats.status.success());
+    assert!(
+        stats.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&stats.stderr)
+    );
+}
+
+#[test]
+fn tree_should_print_dot() {
+    let tree = compressor(&["tree", "-f", "dot", "small.txt"]);
+
+    assert!(tree.status.success());
+    assert!(String::from_utf8(tree.stdout)
+        .unwrap()
+        .starts_with("digraph huffman {"));
+}
+
+#[test]
+fn missing_file_should_log_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = compressor(&[arg(&dir.path().join("missing.txt"))]);
+
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("Error reading File"));
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn unknown_tokenizer_should_fail() {
+    let output = compressor(&["-t", "lines", "small.txt"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown tokenizer 'lines'"));
+}
@@ -0,0 +1,35 @@
+//! Runs under node with
+//! `cargo test --target wasm32-unknown-unknown --no-default-features --features wasm --test wasm`,
+//! using `wasm-bindgen-test-runner` as the target runner (see `.cargo/config.toml`).
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use compressor::wasm::{compress, decompress};
+
+const TEXT: &str = include_str!("../small.txt");
+
+#[wasm_bindgen_test]
+fn compress_should_round_trip() {
+    let compressed = compress(TEXT.as_bytes(), None, None, None, None).unwrap();
+    assert_eq!(decompress(&compressed).unwrap(), TEXT.as_bytes());
+}
+
+#[wasm_bindgen_test]
+fn options_should_round_trip() {
+    let compressed = compress(
+        TEXT.as_bytes(),
+        Some("bytes".to_string()),
+        Some(true),
+        Some(true),
+        Some(64),
+    )
+    .unwrap();
+    assert_eq!(decompress(&compressed).unwrap(), TEXT.as_bytes());
+}
+
+#[wasm_bindgen_test]
+fn invalid_input_should_fail() {
+    assert!(compress(b"aaaa", Some("nope".to_string()), None, None, None).is_err());
+    assert!(decompress(b"not huffman").is_err());
+}